@@ -1,9 +1,8 @@
 #version 400
 
 uniform mat4 screen_to_world_matrix;
-
-const vec4 color1 = vec4(vec3(210.0, 163.0, 36.0) / 255.0, 1.0);
-const vec4 color2 = vec4(vec3(72.0, 67.0, 54.0) / 255.0, 1.0);
+uniform vec3 color1;
+uniform vec3 color2;
 
 in vec2 position;
 out vec4 color;
@@ -23,7 +22,7 @@ void main() {
     vec4 world_coords = screen_to_world_matrix * vec4(p * d, c * d, d);
     vec2 tile_value = mod(world_coords.xy / 10.0, 1.0);
     if (tile_value.x > 0.5 && tile_value.y > 0.5)
-        color = color1;
+        color = vec4(color1, 1.0);
     else
-        color = color2;
+        color = vec4(color2, 1.0);
 }
\ No newline at end of file
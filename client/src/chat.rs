@@ -0,0 +1,158 @@
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use shared::consts::CHAT_LOG_LENGTH;
+use shared::consts::MAX_CHAT_MESSAGE_LENGTH;
+use shared::consts::VISIBLE_CHAT_LINES;
+use shared::consts::chat_line_display_duration;
+use shared::chat::ChatMessage;
+use shared::model::Model;
+
+struct ChatLine {
+    text: String,
+    receive_time: Instant,
+}
+
+struct ChatInput {
+    text: String,
+    team_only: bool,
+}
+
+pub struct Chat {
+    // newest line last
+    log: VecDeque<ChatLine>,
+    input: Option<ChatInput>,
+    // characters typed since the last input handling
+    pending_chars: Vec<char>,
+    // number of lines scrolled back from the newest line
+    scroll: usize,
+}
+
+impl Chat {
+    pub fn new() -> Chat {
+        Chat {
+            log: VecDeque::new(),
+            input: None,
+            pending_chars: Vec::new(),
+            scroll: 0,
+        }
+    }
+
+    pub fn typing(&self) -> bool {
+        self.input.is_some()
+    }
+
+    pub fn start_typing(&mut self, team_only: bool) {
+        // the key that opened the chat must not end up in the text
+        self.pending_chars.clear();
+        self.input = Some(ChatInput {
+            text: String::new(),
+            team_only,
+        });
+    }
+
+    pub fn receive_character(&mut self, c: char) {
+        if self.typing() {
+            self.pending_chars.push(c);
+        }
+    }
+
+    // Returns the text and whether it's for the team only, once the player presses enter.
+    pub fn handle_input(&mut self) -> Option<(String, bool)> {
+        let mut result = None;
+        for c in self.pending_chars.drain(..) {
+            let done = match self.input {
+                Some(ref mut input) => match c {
+                    '\r' | '\n' => {
+                        if !input.text.trim().is_empty() {
+                            result = Some((input.text.clone(), input.team_only));
+                        }
+                        true
+                    },
+                    // escape
+                    '\u{1b}' => true,
+                    // backspace
+                    '\u{8}' => {
+                        input.text.pop();
+                        false
+                    },
+                    c if c.is_control() => false,
+                    c => {
+                        if input.text.chars().count() < MAX_CHAT_MESSAGE_LENGTH {
+                            input.text.push(c);
+                        }
+                        false
+                    },
+                },
+                None => break,
+            };
+            if done {
+                self.input = None;
+                self.scroll = 0;
+            }
+        }
+        result
+    }
+
+    pub fn scroll_up(&mut self) {
+        let max_scroll = self.log.len().saturating_sub(VISIBLE_CHAT_LINES);
+        self.scroll = (self.scroll + 1).min(max_scroll);
+    }
+
+    pub fn scroll_down(&mut self) {
+        self.scroll = self.scroll.saturating_sub(1);
+    }
+
+    pub fn add_message(&mut self, msg: &ChatMessage, model: Option<&Model>) {
+        let name = model.and_then(|m| m.player(msg.player_id)).map(|p| p.name());
+        let text = match (name, msg.team_only) {
+            (Some(name), false) => format!("{}: {}", name, msg.text),
+            (Some(name), true) => format!("[team] {}: {}", name, msg.text),
+            (None, false) => format!("#{}: {}", msg.player_id, msg.text),
+            (None, true) => format!("[team] #{}: {}", msg.player_id, msg.text),
+        };
+        self.add_line(text);
+    }
+
+    // For messages from the game itself, like players joining.
+    pub fn add_notice(&mut self, text: String) {
+        self.add_line(format!("* {}", text));
+    }
+
+    fn add_line(&mut self, text: String) {
+        self.log.push_back(ChatLine {
+            text,
+            receive_time: Instant::now(),
+        });
+        if self.log.len() > CHAT_LOG_LENGTH {
+            self.log.pop_front();
+        }
+        // keep showing the same lines when scrolled back
+        if self.scroll > 0 {
+            self.scroll_up();
+        }
+    }
+
+    // The lines to show, oldest first.
+    // Old lines are hidden, unless the player is typing.
+    pub fn visible_lines(&self) -> Vec<&str> {
+        let now = Instant::now();
+        let end = self.log.len() - self.scroll;
+        let start = end.saturating_sub(VISIBLE_CHAT_LINES);
+        self.log.iter()
+            .skip(start)
+            .take(end - start)
+            .filter(|line| self.typing() || now - line.receive_time < chat_line_display_duration())
+            .map(|line| line.text.as_ref())
+            .collect()
+    }
+
+    // The line that is currently typed, if any.
+    pub fn input_line(&self) -> Option<String> {
+        self.input.as_ref().map(|input| if input.team_only {
+            format!("(team) > {}_", input.text)
+        } else {
+            format!("> {}_", input.text)
+        })
+    }
+}
\ No newline at end of file
@@ -6,12 +6,18 @@ use std::io::Write;
 use toml;
 
 use shared::consts::CLIENT_CONFIG_FILE;
+use shared::consts::DEFAULT_PLAYER_NAME;
 use shared::ConfigParseError;
+use shared::net::Transport;
 use controls::Controls;
 
 pub struct Config {
     pub controls: Controls,
     pub direct_camera: bool,
+    pub transport: Transport,
+    pub name: String,
+    // join without a character
+    pub spectator: bool,
 }
 
 impl Config {
@@ -54,6 +60,47 @@ impl Config {
                     String::from("No graphics section in config!")))
             }
 
+            // the network section is optional, so old configs stay valid
+            let mut transport = Transport::Udp;
+            if let Some(network) = map.get("network") {
+                if let &toml::Value::Table(ref map) = network {
+                    if let Some(value) = map.get("Transport") {
+                        transport = match value.as_str() {
+                            Some("Udp") => Transport::Udp,
+                            Some("Tcp") => Transport::Tcp,
+                            _ => return Err(ConfigParseError(
+                                String::from("Transport must be \"Udp\" or \"Tcp\"!"))),
+                        };
+                    }
+                } else {
+                    return Err(ConfigParseError(String::from("Network is not a table!")))
+                }
+            }
+
+            // the player section is optional as well
+            let mut name = String::from(DEFAULT_PLAYER_NAME);
+            let mut spectator = false;
+            if let Some(player) = map.get("player") {
+                if let &toml::Value::Table(ref map) = player {
+                    if let Some(value) = map.get("Name") {
+                        name = match value.as_str() {
+                            Some(s) => String::from(s),
+                            None => return Err(ConfigParseError(
+                                String::from("Name is not a String!"))),
+                        };
+                    }
+                    if let Some(value) = map.get("Spectator") {
+                        spectator = match value.as_bool() {
+                            Some(b) => b,
+                            None => return Err(ConfigParseError(
+                                String::from("Spectator is not a Boolean!"))),
+                        };
+                    }
+                } else {
+                    return Err(ConfigParseError(String::from("Player is not a table!")))
+                }
+            }
+
             let config = Config {
                 controls: match map.get("controls") {
                     Some(value) => Controls::from_toml(value)?,
@@ -61,6 +108,9 @@ impl Config {
                         String::from("No controls section in config!")))
                 },
                 direct_camera,
+                transport,
+                name,
+                spectator,
             };
             Ok(config)
         } else {
@@ -69,11 +119,22 @@ impl Config {
     }
 
     pub fn to_toml(&self) -> toml::value::Value {
+        let transport = match self.transport {
+            Transport::Udp => "Udp",
+            Transport::Tcp => "Tcp",
+        };
         toml::Value::Table(vec![
             (String::from("controls"), self.controls.to_toml()),
             (String::from("graphics"), toml::Value::Table(vec![
                 (String::from("DirectCamera"), toml::Value::Boolean(self.direct_camera))
-            ].into_iter().collect()))
+            ].into_iter().collect())),
+            (String::from("network"), toml::Value::Table(vec![
+                (String::from("Transport"), toml::Value::String(String::from(transport)))
+            ].into_iter().collect())),
+            (String::from("player"), toml::Value::Table(vec![
+                (String::from("Name"), toml::Value::String(self.name.clone())),
+                (String::from("Spectator"), toml::Value::Boolean(self.spectator)),
+            ].into_iter().collect())),
         ].into_iter().collect())
     }
 }
@@ -83,6 +144,9 @@ impl Default for Config {
         Config {
             controls: Default::default(),
             direct_camera: true,
+            transport: Transport::Udp,
+            name: String::from(DEFAULT_PLAYER_NAME),
+            spectator: false,
         }
     }
 }
\ No newline at end of file
@@ -399,6 +399,14 @@ impl Default for Controls {
             Fire(Button(ScanCode(57)), Jump),
             Fire(Button(KeyCode(VirtualKeyCode::Q)), Exit),
             Fire(Button(KeyCode(VirtualKeyCode::Escape)), ToggleMenu),
+            Fire(Button(KeyCode(VirtualKeyCode::M)), SwitchTeam),
+            Fire(Button(KeyCode(VirtualKeyCode::T)), Chat),
+            Fire(Button(KeyCode(VirtualKeyCode::Y)), TeamChat),
+            Fire(Button(KeyCode(VirtualKeyCode::PageUp)), ChatScrollUp),
+            Fire(Button(KeyCode(VirtualKeyCode::PageDown)), ChatScrollDown),
+            Fire(Button(KeyCode(VirtualKeyCode::O)), ToggleSpectator),
+            Fire(Button(KeyCode(VirtualKeyCode::Right)), SpectateNext),
+            Fire(Button(KeyCode(VirtualKeyCode::Left)), SpectatePrev),
             Fire(MouseWheelTick(Up), PrevWeapon),
             Fire(MouseWheelTick(Down), NextWeapon),
             Value(Axis(0), Yaw),
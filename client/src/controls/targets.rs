@@ -7,6 +7,14 @@ pub enum FireTarget {
     Jump,
     NextWeapon,
     PrevWeapon,
+    SwitchTeam,
+    Chat,
+    TeamChat,
+    ChatScrollUp,
+    ChatScrollDown,
+    ToggleSpectator,
+    SpectateNext,
+    SpectatePrev,
     Exit,
     ToggleMenu,
 }
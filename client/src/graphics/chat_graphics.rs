@@ -0,0 +1,88 @@
+use std::fs::File;
+
+use glium::Display;
+use glium::Frame;
+
+use glium_text;
+use glium_text::TextSystem;
+use glium_text::FontTexture;
+use glium_text::TextDisplay;
+
+use cgmath::Matrix4;
+use cgmath::Vector3;
+use cgmath::SquareMatrix;
+
+use shared::consts::OPTIMAL_SCREEN_RATIO;
+use shared::consts::DEBUG_TEXT_FONT_SIZE;
+use shared::consts::DEBUG_TEXT_HEIGHT;
+use shared::consts::DEBUG_TEXT_RELATIVE_LINE_HEIGHT;
+
+use chat::Chat;
+
+// Draws the chat log and input line in the bottom left corner.
+pub struct ChatGraphics {
+    text_system: TextSystem,
+    font: FontTexture,
+    chat_text_matrix: Matrix4<f32>,
+}
+
+impl ChatGraphics {
+    pub fn new(display: &Display) -> ChatGraphics {
+        let font_file = File::open("SourceCodeVariable-Roman.ttf").expect("Could not load font!");
+        let font = FontTexture::new(display, &font_file, DEBUG_TEXT_FONT_SIZE).unwrap();
+
+        ChatGraphics {
+            text_system: TextSystem::new(display),
+            font,
+            chat_text_matrix: Matrix4::identity().into(),
+        }
+    }
+
+    pub fn draw(&self, frame: &mut Frame, chat: &Chat) {
+        // line 0 is the bottom line
+        let mut line_number = 0;
+        if let Some(input_line) = chat.input_line() {
+            self.draw_chat_line(frame, line_number, &input_line, (1.0, 1.0, 0.5, 1.0));
+            line_number += 1;
+        }
+        for line in chat.visible_lines().iter().rev() {
+            self.draw_chat_line(frame, line_number, line, (1.0, 1.0, 1.0, 1.0));
+            line_number += 1;
+        }
+    }
+
+    fn draw_chat_line(&self, frame: &mut Frame, line_number: u64, text: &str,
+                      color: (f32, f32, f32, f32)) {
+        let text_display = TextDisplay::new(&self.text_system, &self.font, text);
+
+        let y_offset = DEBUG_TEXT_RELATIVE_LINE_HEIGHT * (line_number + 1) as f64;
+        let x_offset = DEBUG_TEXT_RELATIVE_LINE_HEIGHT - 1.0;
+        let translation = Vector3::new(x_offset as f32, y_offset as f32, 0.0);
+
+        let trafo_matrix = Matrix4::from_translation(translation);
+        let matrix: [[f32; 4]; 4] = (self.chat_text_matrix * trafo_matrix).into();
+
+        glium_text::draw(&text_display, &self.text_system, frame, matrix, color);
+    }
+
+    pub fn set_screen_ratio(&mut self, screen_ratio: f64) {
+        let scaling_factor;
+        let mut x_offset = 0.0;
+        let mut y_offset = 0.0;
+        if screen_ratio > OPTIMAL_SCREEN_RATIO {
+            scaling_factor = OPTIMAL_SCREEN_RATIO / screen_ratio;
+            x_offset = 1.0 - scaling_factor as f32;
+        } else {
+            scaling_factor = screen_ratio / OPTIMAL_SCREEN_RATIO;
+            y_offset = 1.0 - scaling_factor as f32;
+        }
+        let y_scaling = (DEBUG_TEXT_HEIGHT * scaling_factor) as f32;
+        let x_scaling = (DEBUG_TEXT_HEIGHT * scaling_factor / screen_ratio) as f32;
+        self.chat_text_matrix = Matrix4::new(
+            x_scaling,      0.0,            0.0, 0.0,
+            0.0,            y_scaling,      0.0, 0.0,
+            0.0,            0.0,            1.0, 0.0,
+            -1.0 + x_offset, -1.0 + y_offset, 0.0, 1.0f32,
+        );
+    }
+}
\ No newline at end of file
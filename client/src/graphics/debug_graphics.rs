@@ -14,15 +14,21 @@ use cgmath::Vector3;
 use cgmath::SquareMatrix;
 
 use shared::consts::OPTIMAL_SCREEN_RATIO;
+use shared::consts::TICK_SPEED;
 use shared::consts::DEBUG_TEXT_FONT_SIZE;
 use shared::consts::DEBUG_TEXT_HEIGHT;
 use shared::consts::DEBUG_TEXT_RELATIVE_LINE_HEIGHT;
+use shared::model::world::character::WEAPONS;
 
 use server_interface::ConnectionState;
 
 pub struct DebugGraphics {
     tick_buffer: String,
     num_players_buffer: String,
+    health_buffer: String,
+    weapons_buffer: String,
+    match_buffer: String,
+    score_buffer: String,
     text_system: TextSystem,
     font: FontTexture,
     debug_text_matrix: Matrix4<f32>,
@@ -36,6 +42,10 @@ impl DebugGraphics {
         DebugGraphics {
             tick_buffer: String::new(),
             num_players_buffer: String::new(),
+            health_buffer: String::new(),
+            weapons_buffer: String::new(),
+            match_buffer: String::new(),
+            score_buffer: String::new(),
             text_system: TextSystem::new(display),
             font,
             debug_text_matrix: Matrix4::identity().into(),
@@ -46,24 +56,118 @@ impl DebugGraphics {
         let tick_text;
         let connection_state_text;
         let num_players_text;
+        let health_text;
+        let weapons_text;
+        let match_text;
+        let score_text;
         match connection_state {
-            ConnectionState::Connected { tick_instant, model, .. } => {
-                let num_players = model.world().characters().len();
+            ConnectionState::Connected { tick_instant, model, my_player_id, .. } => {
+                let num_players = model.world().characters().count();
+                let my_character = model.player(my_player_id)
+                    .and_then(|p| p.character_id())
+                    .and_then(|id| model.world().character(id));
 
                 self.tick_buffer.clear();
                 self.num_players_buffer.clear();
+                self.health_buffer.clear();
+                self.weapons_buffer.clear();
+                self.match_buffer.clear();
+                self.score_buffer.clear();
 
                 write!(&mut self.tick_buffer, "{}", tick_instant.tick).unwrap();
                 write!(&mut self.num_players_buffer, "{}", num_players).unwrap();
+                match my_character {
+                    Some(c) if c.alive() => {
+                        write!(&mut self.health_buffer, "{} / {}", c.health(), c.armor()).unwrap();
+                    },
+                    Some(_) => write!(&mut self.health_buffer, "dead").unwrap(),
+                    None if model.player(my_player_id).is_some() => {
+                        write!(&mut self.health_buffer, "spectating").unwrap()
+                    },
+                    None => write!(&mut self.health_buffer, "---").unwrap(),
+                }
+                match my_character {
+                    Some(c) if c.alive() => {
+                        // the selected weapon is in brackets
+                        let selected = c.input().weapon;
+                        for &weapon in WEAPONS.iter().filter(|&&weapon| c.has_weapon(weapon)) {
+                            if !self.weapons_buffer.is_empty() {
+                                write!(&mut self.weapons_buffer, " ").unwrap();
+                            }
+                            let (open, close) = if weapon == selected {
+                                ("[", "]")
+                            } else {
+                                ("", "")
+                            };
+                            match c.ammo(weapon) {
+                                Some(ammo) => write!(
+                                    &mut self.weapons_buffer,
+                                    "{}{:?} {}{}",
+                                    open,
+                                    weapon,
+                                    ammo,
+                                    close,
+                                ).unwrap(),
+                                None => write!(
+                                    &mut self.weapons_buffer,
+                                    "{}{:?}{}",
+                                    open,
+                                    weapon,
+                                    close,
+                                ).unwrap(),
+                            }
+                        }
+                    },
+                    _ => write!(&mut self.weapons_buffer, "---").unwrap(),
+                }
+                let game_mode = model.game_mode();
+                write!(&mut self.match_buffer, "{:?}", game_mode.phase()).unwrap();
+                if let Some(remaining_ticks) = game_mode.remaining_ticks() {
+                    let remaining_secs = remaining_ticks / TICK_SPEED.per_second();
+                    write!(
+                        &mut self.match_buffer,
+                        " {}:{:02}",
+                        remaining_secs / 60,
+                        remaining_secs % 60,
+                    ).unwrap();
+                }
+                for (i, team) in model.teams().iter().enumerate() {
+                    let separator = if i == 0 { " | " } else { " - " };
+                    write!(
+                        &mut self.match_buffer,
+                        "{}{} {}",
+                        separator,
+                        team.name(),
+                        team.score(),
+                    ).unwrap();
+                }
+                match model.player(my_player_id) {
+                    Some(p) => write!(
+                        &mut self.score_buffer,
+                        "{} ({} kills, {} deaths)",
+                        p.score(),
+                        p.kills(),
+                        p.deaths(),
+                    ).unwrap(),
+                    None => write!(&mut self.score_buffer, "---").unwrap(),
+                }
 
                 tick_text = self.tick_buffer.as_ref();
                 connection_state_text = "connected";
                 num_players_text = self.num_players_buffer.as_ref();
+                health_text = self.health_buffer.as_ref();
+                weapons_text = self.weapons_buffer.as_ref();
+                match_text = self.match_buffer.as_ref();
+                score_text = self.score_buffer.as_ref();
             },
             _ => {
                 tick_text = "---";
                 connection_state_text = "---";
                 num_players_text = "---";
+                health_text = "---";
+                weapons_text = "---";
+                match_text = "---";
+                score_text = "---";
             },
         }
 
@@ -72,10 +176,18 @@ impl DebugGraphics {
                     Connection state: {}\n\
                     Tick: {}\n\
                     Num players: {}\n\
+                    Health / armor: {}\n\
+                    Weapons: {}\n\
+                    Match: {}\n\
+                    Score: {}\n\
                 ",
             connection_state_text,
             tick_text,
-            num_players_text
+            num_players_text,
+            health_text,
+            weapons_text,
+            match_text,
+            score_text
         );
 
         for (i, line) in debug_text.lines().enumerate() {
@@ -1,5 +1,6 @@
 mod model_graphics;
 mod debug_graphics;
+mod chat_graphics;
 
 use glium::Display;
 use glium::Surface;
@@ -7,13 +8,17 @@ use glium::Surface;
 use shared::model::world::character::ViewDir;
 
 use server_interface::ConnectionState;
+use chat::Chat;
+use spectator_camera::SpectatorCamera;
 
 use self::model_graphics::ModelGraphics;
 use self::debug_graphics::DebugGraphics;
+use self::chat_graphics::ChatGraphics;
 
 pub struct Graphics {
     model_graphics: ModelGraphics,
     debug_graphics: DebugGraphics,
+    chat_graphics: ChatGraphics,
 }
 
 impl Graphics {
@@ -21,10 +26,12 @@ impl Graphics {
         Graphics {
             model_graphics: ModelGraphics::new(display),
             debug_graphics: DebugGraphics::new(display),
+            chat_graphics: ChatGraphics::new(display),
         }
     }
 
-    pub fn draw(&mut self, connection_state: ConnectionState, view_dir: Option<ViewDir>,
+    pub fn draw(&mut self, connection_state: ConnectionState, chat: &Chat,
+                spectator_camera: &SpectatorCamera, view_dir: Option<ViewDir>,
                 display: &Display) {
         let mut frame = display.draw();
         frame.clear_color(0.0, 0.0, 0.0, 1.0);
@@ -37,12 +44,15 @@ impl Graphics {
                 my_player_id,
                 model,
                 predicted_world,
+                map,
             } => {
                 self.model_graphics.draw(
                     model,
                     predicted_world,
+                    map.lighting(),
                     my_player_id,
                     view_dir,
+                    spectator_camera,
                     tick_instant,
                     &mut frame,
                 );
@@ -52,6 +62,7 @@ impl Graphics {
         }
 
         self.debug_graphics.draw(&mut frame, connection_state);
+        self.chat_graphics.draw(&mut frame, chat);
 
         frame.finish().unwrap();
     }
@@ -64,5 +75,6 @@ impl Graphics {
         };
         self.model_graphics.set_screen_ratio(ratio);
         self.debug_graphics.set_screen_ratio(ratio);
+        self.chat_graphics.set_screen_ratio(ratio);
     }
 }
\ No newline at end of file
@@ -22,16 +22,23 @@ use cgmath::Rad;
 use cgmath::PerspectiveFov;
 
 use shared::tick_time::TickInstant;
+use shared::math::FPAngle;
 use shared::model::Model;
 use shared::model::world::World;
 use shared::model::world::character::ViewDir;
+use shared::map::Lighting;
 use shared::consts::Y_FOV;
 use shared::consts::OPTIMAL_SCREEN_RATIO;
 use shared::consts::Z_NEAR;
 use shared::consts::Z_FAR;
+use shared::consts::PROJECTILE_COLOR;
+
+use spectator_camera::SpectatorCamera;
 
 use self::visual_world::VisualWorld;
 use self::visual_world::VisualCharacter;
+use self::visual_world::VisualProjectile;
+use self::visual_world::VisualPickup;
 
 #[derive(Copy, Clone)]
 struct MyVertex {
@@ -139,9 +146,10 @@ impl ModelGraphics {
         }
     }
 
-    pub fn draw(&mut self, current_model: &Model, predicted_world: &World, my_player_id: u64,
-                view_dir: Option<ViewDir>, tick_instant: TickInstant, frame: &mut Frame) {
-        let current_world = current_model.world();
+    pub fn draw(&mut self, current_model: &Model, predicted_world: &World, lighting: &Lighting,
+                my_player_id: u64, view_dir: Option<ViewDir>,
+                spectator_camera: &SpectatorCamera, tick_instant: TickInstant,
+                frame: &mut Frame) {
         let my_character_id = current_model.player(my_player_id)
                 .and_then(|p| p.character_id());
         if self.current_tick != tick_instant.tick {
@@ -149,7 +157,7 @@ impl ModelGraphics {
             mem::swap(&mut self.last_visual_world, &mut self.current_visual_world);
         }
         self.current_tick = tick_instant.tick;
-        self.current_visual_world.rebuild(my_character_id, current_world, predicted_world);
+        self.current_visual_world.rebuild(my_character_id, current_model, predicted_world);
 
         let tick_diff = (self.current_tick - self.last_tick) as f64;
         self.mix_world.remix(
@@ -158,20 +166,33 @@ impl ModelGraphics {
             (tick_diff - 1.0 + tick_instant.intra_tick) / tick_diff
         );
 
-        let character = if let Some(c) = my_character_id.and_then(
-            |id| self.current_visual_world.character(id)
-        ) {
-            c
+        // spectators look through the eyes of the followed player or fly freely
+        let camera_character_id = my_character_id
+            .or_else(|| spectator_camera.target_character_id(current_model));
+        // our own character is predicted, others are interpolated
+        let camera_visual_world = if my_character_id.is_some() {
+            &self.current_visual_world
         } else {
-            return
+            &self.mix_world
+        };
+        let (camera_pos, mut yaw, mut pitch) = match camera_character_id.and_then(
+            |id| camera_visual_world.character(id)
+        ) {
+            Some(c) => (c.pos(), c.yaw(), c.pitch()),
+            None if my_character_id.is_none() => {
+                let vd = spectator_camera.view_dir();
+                (spectator_camera.pos(), vd.yaw().rad_f32(), vd.pitch().rad_f32())
+            },
+            None => return,
         };
-
-        let mut yaw = character.yaw();
-        let mut pitch = character.pitch();
 
         // overwrite with direct camera
-        if let Some(vd) = view_dir {
-            yaw = vd.yaw().rad_f32();
+        if let (Some(vd), Some(id)) = (view_dir, my_character_id) {
+            // spawn points and teleporters may have turned the character
+            let yaw_offset = predicted_world.character(id)
+                .map(|c| c.yaw_offset())
+                .unwrap_or(FPAngle::zero());
+            yaw = (vd.yaw() + yaw_offset).rad_f32();
             pitch = vd.pitch().rad_f32();
         }
 
@@ -179,26 +200,39 @@ impl ModelGraphics {
         let world_to_character_matrix =
             Matrix4::from_angle_y(Rad(pitch as f32))
             * Matrix4::from_angle_z(Rad(-yaw as f32))
-            * Matrix4::from_translation(-character.pos());
+            * Matrix4::from_translation(-camera_pos);
 
         // world cs to screen cs
         let world_to_screen_matrix = self.perspective_matrix * world_to_character_matrix;
 
-        self.draw_background(frame, &world_to_screen_matrix);
+        self.draw_background(lighting, frame, &world_to_screen_matrix);
 
         for (id, character) in self.mix_world.characters() {
-            if Some(*id) == my_character_id {
+            if Some(*id) == camera_character_id {
                 continue;
             }
-            self.draw_character(character, frame, &world_to_screen_matrix);
+            self.draw_character(character, lighting, frame, &world_to_screen_matrix);
+        }
+
+        let projectiles = self.mix_world.projectiles().values()
+            .chain(self.mix_world.predicted_projectiles().values());
+        for projectile in projectiles {
+            self.draw_projectile(projectile, lighting, frame, &world_to_screen_matrix);
+        }
+
+        for pickup in self.mix_world.pickups().values() {
+            self.draw_pickup(pickup, lighting, frame, &world_to_screen_matrix);
         }
     }
 
-    fn draw_background(&self, frame: &mut Frame, world_to_screen_matrix: &Matrix4<f32>) {
+    fn draw_background(&self, lighting: &Lighting, frame: &mut Frame,
+                       world_to_screen_matrix: &Matrix4<f32>) {
         let screen_to_world_matrix_uniform: [[f32; 4]; 4]
             = world_to_screen_matrix.invert().unwrap().into();
         let background_uniforms = uniform! {
-            screen_to_world_matrix: screen_to_world_matrix_uniform
+            screen_to_world_matrix: screen_to_world_matrix_uniform,
+            color1: lighting.background_color_1,
+            color2: lighting.background_color_2,
         };
 
         frame.draw(
@@ -210,7 +244,7 @@ impl ModelGraphics {
         ).unwrap();
     }
 
-    fn draw_character(&self, character: &VisualCharacter,
+    fn draw_character(&self, character: &VisualCharacter, lighting: &Lighting,
                       frame: &mut Frame, world_to_screen_matrix: &Matrix4<f32>) {
         // character cs to world cs
         let character_to_world_matrix = Matrix4::from_translation(character.pos().into());
@@ -219,15 +253,41 @@ impl ModelGraphics {
             * Matrix4::from_angle_z(Rad(character.yaw() as f32))
             * Matrix4::from_angle_y(Rad(-character.pitch() as f32));
 
+        self.draw_head_object(&head_to_world_matrix, character.color(), lighting, frame,
+                              world_to_screen_matrix);
+    }
+
+    fn draw_projectile(&self, projectile: &VisualProjectile, lighting: &Lighting,
+                       frame: &mut Frame, world_to_screen_matrix: &Matrix4<f32>) {
+        // TODO use a proper projectile model instead of a small head
+        let projectile_to_world_matrix = Matrix4::from_translation(projectile.pos())
+            * Matrix4::from_scale(0.5);
+        self.draw_head_object(&projectile_to_world_matrix, PROJECTILE_COLOR, lighting, frame,
+                              world_to_screen_matrix);
+    }
+
+    fn draw_pickup(&self, pickup: &VisualPickup, lighting: &Lighting,
+                   frame: &mut Frame, world_to_screen_matrix: &Matrix4<f32>) {
+        // TODO use proper pickup models instead of heads
+        let pickup_to_world_matrix = Matrix4::from_translation(pickup.pos())
+            * Matrix4::from_scale(0.8);
+        self.draw_head_object(&pickup_to_world_matrix, pickup.color(), lighting, frame,
+                              world_to_screen_matrix);
+    }
+
+    fn draw_head_object(&self, object_to_world_matrix: &Matrix4<f32>, color: [f32; 3],
+                        lighting: &Lighting, frame: &mut Frame,
+                        world_to_screen_matrix: &Matrix4<f32>) {
         // uniforms
-        let head_to_world_matrix_uniform: [[f32; 4]; 4] = head_to_world_matrix.into();
+        let object_to_world_matrix_uniform: [[f32; 4]; 4] = (*object_to_world_matrix).into();
         let world_to_screen_matrix_uniform: [[f32; 4]; 4] = (*world_to_screen_matrix).into();
         let uniforms = uniform! {
-            object_to_world_matrix:      head_to_world_matrix_uniform,
+            object_to_world_matrix:      object_to_world_matrix_uniform,
             world_to_screen_matrix:      world_to_screen_matrix_uniform,
-            ambient_light_color:         [0.30, 0.30, 0.35f32],
-            directional_light_dir:       [1.00, 2.00, 3.00f32],
-            directional_light_color:     [0.50, 0.50, 0.50f32],
+            ambient_light_color:         lighting.ambient_light_color,
+            directional_light_dir:       lighting.directional_light_dir,
+            directional_light_color:     lighting.directional_light_color,
+            object_color:                color,
         };
 
         // draw parameters
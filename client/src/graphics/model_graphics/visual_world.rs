@@ -4,18 +4,29 @@ use std::collections::HashMap;
 use cgmath::Vector3;
 
 use shared::util::Mix;
+use shared::consts::DEFAULT_CHARACTER_COLOR;
+use shared::consts::HEALTH_PICKUP_COLOR;
+use shared::consts::ARMOR_PICKUP_COLOR;
+use shared::consts::AMMO_PICKUP_COLOR;
+use shared::consts::WEAPON_PICKUP_COLOR;
+use shared::model::Model;
 use shared::model::world::World;
 use shared::model::world::character::Character;
+use shared::model::world::entity::EntityId;
+use shared::model::world::projectile::Projectile;
+use shared::model::world::pickup::PickupSpawn;
+use shared::model::world::pickup::PickupKind;
 
 #[derive(Clone)]
 pub struct VisualCharacter {
     pos: Vector3<f32>,
     yaw: f32,
     pitch: f32,
+    color: [f32; 3],
 }
 
 impl VisualCharacter {
-    pub fn build(character: &Character) -> VisualCharacter {
+    pub fn build(character: &Character, color: [f32; 3]) -> VisualCharacter {
         let wcp = character.pos();
         let wc_yaw = character.view_dir().yaw();
         let wc_pitch = character.view_dir().pitch();
@@ -23,6 +34,7 @@ impl VisualCharacter {
             pos: wcp.into(),
             yaw: wc_yaw.rad_f32(),
             pitch: wc_pitch.rad_f32(),
+            color,
         }
     }
 
@@ -37,6 +49,10 @@ impl VisualCharacter {
     pub fn pitch(&self) -> f32 {
         self.pitch
     }
+
+    pub fn color(&self) -> [f32; 3] {
+        self.color
+    }
 }
 
 impl Mix for VisualCharacter {
@@ -52,34 +68,125 @@ impl Mix for VisualCharacter {
             pos: self.pos.mix(&other.pos, ratio),
             yaw: self.yaw.mix(&oy, ratio),
             pitch: self.pitch.mix(&other.pitch, ratio),
+            color: other.color,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct VisualProjectile {
+    pos: Vector3<f32>,
+}
+
+impl VisualProjectile {
+    pub fn build(projectile: &Projectile) -> VisualProjectile {
+        VisualProjectile {
+            pos: projectile.pos().into(),
+        }
+    }
+
+    pub fn pos(&self) -> Vector3<f32> {
+        self.pos
+    }
+}
+
+impl Mix for VisualProjectile {
+    fn mix(&self, other: &Self, ratio: f64) -> Self {
+        VisualProjectile {
+            pos: self.pos.mix(&other.pos, ratio),
         }
     }
 }
 
+// Pickups don't move, so they aren't mixed.
+#[derive(Clone)]
+pub struct VisualPickup {
+    pos: Vector3<f32>,
+    color: [f32; 3],
+}
+
+impl VisualPickup {
+    pub fn build(spawn: &PickupSpawn) -> VisualPickup {
+        let color = match spawn.kind {
+            PickupKind::Health => HEALTH_PICKUP_COLOR,
+            PickupKind::Armor => ARMOR_PICKUP_COLOR,
+            PickupKind::Ammo(_) => AMMO_PICKUP_COLOR,
+            PickupKind::Weapon(_) => WEAPON_PICKUP_COLOR,
+        };
+        VisualPickup {
+            pos: spawn.pos.into(),
+            color,
+        }
+    }
+
+    pub fn pos(&self) -> Vector3<f32> {
+        self.pos
+    }
+
+    pub fn color(&self) -> [f32; 3] {
+        self.color
+    }
+}
+
 #[derive(Clone)]
 pub struct VisualWorld {
-    characters: HashMap<u64, VisualCharacter>,
+    characters: HashMap<EntityId, VisualCharacter>,
+    projectiles: HashMap<EntityId, VisualProjectile>,
+    // ids of the predicted world, they can be taken by other entities on the server
+    predicted_projectiles: HashMap<EntityId, VisualProjectile>,
+    // by index of the level's pickups
+    pickups: HashMap<usize, VisualPickup>,
 }
 
 impl VisualWorld {
     pub fn new() -> VisualWorld {
         VisualWorld {
             characters: HashMap::new(),
+            projectiles: HashMap::new(),
+            predicted_projectiles: HashMap::new(),
+            pickups: HashMap::new(),
         }
     }
 
-    pub fn rebuild(&mut self, my_character_id: Option<u64>,
-                   current_world: &World, predicted_world: &World) {
+    pub fn rebuild(&mut self, my_character_id: Option<EntityId>,
+                   current_model: &Model, predicted_world: &World) {
         self.reset();
-        for (&id, c) in  current_world.characters() {
-            if Some(id) == my_character_id {
+        let current_world = current_model.world();
+        let mut colors = HashMap::new();
+        for player in current_model.players().values() {
+            let team = player.team().and_then(|team| current_model.teams().get(team as usize));
+            if let (Some(character_id), Some(team)) = (player.character_id(), team) {
+                colors.insert(character_id, team.color());
+            }
+        }
+        let color = |id: EntityId| colors.get(&id).cloned().unwrap_or(DEFAULT_CHARACTER_COLOR);
+        for (id, c) in current_world.characters() {
+            // our own character is still needed for the camera when dead
+            if Some(id) == my_character_id || !c.alive() {
                 continue;
             }
-            self.characters.insert(id, VisualCharacter::build(c));
+            self.characters.insert(id, VisualCharacter::build(c, color(id)));
         }
         if let Some(id) = my_character_id {
             if let Some(character) = predicted_world.character(id) {
-                self.characters.insert(id, VisualCharacter::build(character));
+                self.characters.insert(id, VisualCharacter::build(character, color(id)));
+            }
+        }
+        // our own projectiles are predicted
+        for (id, p) in current_world.projectiles() {
+            if Some(p.owner()) != my_character_id {
+                self.projectiles.insert(id, VisualProjectile::build(p));
+            }
+        }
+        for (id, p) in predicted_world.projectiles() {
+            if Some(p.owner()) == my_character_id {
+                self.predicted_projectiles.insert(id, VisualProjectile::build(p));
+            }
+        }
+        // predicted, so the ones we take disappear right away
+        for (index, spawn, p) in predicted_world.pickups() {
+            if p.available() {
+                self.pickups.insert(index, VisualPickup::build(spawn));
             }
         }
     }
@@ -93,17 +200,49 @@ impl VisualWorld {
                 self.characters.insert(*id, cb.clone()); // always insert characters of the current world
             }
         }
+        for (id, pb) in b.projectiles() {
+            if let Some(pa) = a.projectiles().get(id) {
+                self.projectiles.insert(*id, pa.mix(pb, ratio));
+            } else {
+                self.projectiles.insert(*id, pb.clone());
+            }
+        }
+        for (id, pb) in b.predicted_projectiles() {
+            if let Some(pa) = a.predicted_projectiles().get(id) {
+                self.predicted_projectiles.insert(*id, pa.mix(pb, ratio));
+            } else {
+                self.predicted_projectiles.insert(*id, pb.clone());
+            }
+        }
+        for (index, pb) in b.pickups() {
+            self.pickups.insert(*index, pb.clone());
+        }
     }
 
-    pub fn character(&self, character_id: u64) -> Option<&VisualCharacter> {
+    pub fn character(&self, character_id: EntityId) -> Option<&VisualCharacter> {
         self.characters.get(&character_id)
     }
 
-    pub fn characters(&self) -> &HashMap<u64, VisualCharacter> {
+    pub fn characters(&self) -> &HashMap<EntityId, VisualCharacter> {
         &self.characters
     }
 
+    pub fn projectiles(&self) -> &HashMap<EntityId, VisualProjectile> {
+        &self.projectiles
+    }
+
+    pub fn predicted_projectiles(&self) -> &HashMap<EntityId, VisualProjectile> {
+        &self.predicted_projectiles
+    }
+
+    pub fn pickups(&self) -> &HashMap<usize, VisualPickup> {
+        &self.pickups
+    }
+
     fn reset(&mut self) {
         self.characters.clear();
+        self.projectiles.clear();
+        self.predicted_projectiles.clear();
+        self.pickups.clear();
     }
 }
\ No newline at end of file
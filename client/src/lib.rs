@@ -3,6 +3,8 @@ mod controls;
 mod config;
 mod server_interface;
 mod menu;
+mod chat;
+mod spectator_camera;
 
 #[macro_use] extern crate glium;
 extern crate glium_text;
@@ -11,10 +13,8 @@ extern crate toml;
 extern crate num;
 extern crate strum;
 #[macro_use] extern crate strum_macros;
-extern crate rand;
-extern crate arrayvec;
-
 extern crate shared;
+extern crate net_client;
 
 use std::time::Instant;
 use std::env;
@@ -26,7 +26,11 @@ use glium::backend::glutin::Display;
 use shared::math::FPAngle;
 use shared::consts::BASE_SPEED;
 use shared::consts::DRAW_SPEED;
+use shared::consts::DEFAULT_MAP;
+use shared::map::Map;
 use shared::model::world::character::CharacterInput;
+use shared::model::world::pickup::PickupKind;
+use shared::net::PlayerEvent;
 
 use graphics::Graphics;
 use server_interface::ServerInterface;
@@ -36,6 +40,8 @@ use server_interface::ConnectionState::*;
 use server_interface::HandleTrafficResult;
 use config::Config;
 use menu::Menu;
+use chat::Chat;
+use spectator_camera::SpectatorCamera;
 use TickTarget::*;
 
 enum TickTarget {
@@ -54,6 +60,8 @@ pub struct Client {
     character_input: CharacterInput,
     closing: bool,
     menu: Menu,
+    chat: Chat,
+    spectator_camera: SpectatorCamera,
 }
 
 impl Client {
@@ -81,9 +89,18 @@ impl Client {
         let si: Box<ServerInterface> = match env::args().nth(1) {
             Some(addr_string) => {
                 let mut addrs = addr_string.to_socket_addrs().unwrap();
-                Box::new(RemoteServerInterface::new(addrs.next().unwrap()).unwrap())
+                Box::new(RemoteServerInterface::new(
+                    addrs.next().unwrap(),
+                    config.transport,
+                    config.name.clone(),
+                    config.spectator,
+                ).unwrap())
             },
-            None => Box::new(LocalServerInterface::new()),
+            None => Box::new(LocalServerInterface::new(
+                Map::load(DEFAULT_MAP).expect("Could not load default map!"),
+                config.name.clone(),
+                config.spectator,
+            )),
         };
 
         Client {
@@ -95,6 +112,8 @@ impl Client {
             character_input: Default::default(),
             closing: false,
             menu: Menu::new(),
+            chat: Chat::new(),
+            spectator_camera: SpectatorCamera::new(),
         }
     }
 
@@ -144,6 +163,9 @@ impl Client {
                 };
                 // TODO maybe add conditional break here, to make sure the client stays responsive on DDoS
             }
+            self.receive_chat_messages();
+            self.receive_player_events();
+            self.receive_pickup_events();
 
             // handle closing request
             if self.closing {
@@ -160,6 +182,9 @@ impl Client {
                 GameTick => {
                     let mut character_input = self.character_input;
                     self.server_interface.do_tick(character_input);
+                    if self.spectating() {
+                        self.spectator_camera.fly(&character_input);
+                    }
                     tick_counter += 1;
                 },
                 SocketTick => self.server_interface.do_socket_tick(),
@@ -172,6 +197,8 @@ impl Client {
 
                     self.graphics.draw(
                         self.server_interface.connection_state(),
+                        &self.chat,
+                        &self.spectator_camera,
                         view_dir,
                         &self.display
                     );
@@ -223,6 +250,7 @@ impl Client {
         let closing = &mut self.closing;
         let menu = &mut self.menu;
         let config = &mut self.config;
+        let chat = &mut self.chat;
         self.events_loop.poll_events(|ev| {
             match ev {
                 // Window events are only received if the window has focus
@@ -233,7 +261,7 @@ impl Client {
                     WE::DroppedFile(buf) => println!("File dropped: {:?}", buf),
                     WE::HoveredFile(buf) => println!("File hovered: {:?}", buf),
                     WE::HoveredFileCancelled => println!("File hover canceled"),
-                    WE::ReceivedCharacter(_c) => (), // TODO handle chat
+                    WE::ReceivedCharacter(c) => chat.receive_character(c),
                     WE::Focused(false) => menu.set_active(true),
                     WE::KeyboardInput { device_id, input } =>
                         config.controls.process_keyboard_input_event(device_id, input),
@@ -269,13 +297,30 @@ impl Client {
         let mut character_input = self.character_input;
         let mut yaw_delta = 0.0;
         let mut pitch_delta = 0.0;
+        let mut switch_team = false;
+        let mut start_chat = None;
+        let mut toggle_spectator = false;
+        let mut spectate_next = false;
+        let mut spectate_prev = false;
+        // while typing, the keys belong to the chat
+        let typing = self.chat.typing();
         for ie in self.config.controls.events() {
             match ie {
+                Fire(ChatScrollUp) => self.chat.scroll_up(),
+                Fire(ChatScrollDown) => self.chat.scroll_down(),
+                Fire(_) | Switch { .. } if typing => (),
                 Fire(target) => {
                     match target {
                         Jump => character_input.num_jumps += 1,
-                        NextWeapon => println!("next weapon"),
-                        PrevWeapon => println!("previous weapon"),
+                        NextWeapon => character_input.weapon = character_input.weapon.next(),
+                        PrevWeapon => character_input.weapon = character_input.weapon.prev(),
+                        SwitchTeam => switch_team = true,
+                        Chat => start_chat = Some(false),
+                        TeamChat => start_chat = Some(true),
+                        ChatScrollUp | ChatScrollDown => (), // handled above
+                        ToggleSpectator => toggle_spectator = true,
+                        SpectateNext => spectate_next = true,
+                        SpectatePrev => spectate_prev = true,
                         ToggleMenu => {
                             let menu_active = self.menu.active();
                             self.menu.set_active(!menu_active);
@@ -286,8 +331,8 @@ impl Client {
                 Value { target: Yaw, value } => yaw_delta += value,
                 Value { target: Pitch, value } => pitch_delta += value,
                 Switch { target, state} => match target {
-                    Shoot => if state == Active { println!("pew") },
-                    Aim => if state == Active { println!("aim") },
+                    Shoot => character_input.fire = state == Active,
+                    Aim => character_input.aim = state == Active,
                     MoveForward => character_input.forward = state == Active,
                     MoveBackward => character_input.backward = state == Active,
                     MoveLeft => character_input.left = state == Active,
@@ -298,8 +343,126 @@ impl Client {
         }
         character_input.view_dir.add_yaw(FPAngle::from_tau_float(yaw_delta));
         character_input.view_dir.add_pitch(FPAngle::from_tau_float(pitch_delta));
+        if typing {
+            if let Some((text, team_only)) = self.chat.handle_input() {
+                if text.starts_with("/name ") {
+                    self.server_interface.rename(String::from(&text["/name ".len()..]));
+                } else {
+                    self.server_interface.send_chat(text, team_only);
+                }
+            }
+        } else if let Some(team_only) = start_chat {
+            self.chat.start_typing(team_only);
+            // keys released while typing would never reach the character
+            character_input.forward = false;
+            character_input.backward = false;
+            character_input.left = false;
+            character_input.right = false;
+            character_input.crouch = false;
+            character_input.fire = false;
+            character_input.aim = false;
+        }
         if !self.menu.active() {
             self.character_input = character_input;
+            if switch_team {
+                self.switch_to_next_team();
+            }
+            if toggle_spectator {
+                self.toggle_spectator();
+            }
+            if let Connected { model, .. } = self.server_interface.connection_state() {
+                if spectate_next {
+                    self.spectator_camera.next_target(model);
+                }
+                if spectate_prev {
+                    self.spectator_camera.prev_target(model);
+                }
+            }
+        }
+    }
+
+    fn spectating(&self) -> bool {
+        match self.server_interface.connection_state() {
+            Connected { my_player_id, model, .. } => {
+                model.player(my_player_id).map_or(false, |player| !player.playing())
+            },
+            _ => false,
+        }
+    }
+
+    fn toggle_spectator(&mut self) {
+        let spectator = match self.server_interface.connection_state() {
+            Connected { my_player_id, model, .. } => match model.player(my_player_id) {
+                Some(player) => player.spectator(),
+                None => return,
+            },
+            _ => return,
+        };
+        self.server_interface.set_spectator(!spectator);
+    }
+
+    fn receive_chat_messages(&mut self) {
+        let messages = self.server_interface.take_chat_messages();
+        let model = match self.server_interface.connection_state() {
+            Connected { model, .. } => Some(model),
+            _ => None,
+        };
+        for msg in messages {
+            self.chat.add_message(&msg, model);
+        }
+    }
+
+    // Only our own pickups are announced, the others can be seen in the world.
+    fn receive_pickup_events(&mut self) {
+        let events = self.server_interface.take_pickup_events();
+        let my_character_id = match self.server_interface.connection_state() {
+            Connected { my_player_id, model, .. } => {
+                model.player(my_player_id).and_then(|player| player.character_id())
+            },
+            _ => None,
+        };
+        for event in events {
+            if Some(event.character_id) != my_character_id {
+                continue;
+            }
+            let amount = event.kind.params().amount;
+            let notice = match event.kind {
+                PickupKind::Health => format!("+{} health", amount),
+                PickupKind::Armor => format!("+{} armor", amount),
+                PickupKind::Ammo(weapon) => format!("+{} {:?} ammo", amount, weapon),
+                PickupKind::Weapon(weapon) => format!("Picked up the {:?}", weapon),
+            };
+            self.chat.add_notice(notice);
         }
     }
+
+    fn receive_player_events(&mut self) {
+        for event in self.server_interface.take_player_events() {
+            let notice = match event {
+                PlayerEvent::Joined { name, .. } => format!("{} joined", name),
+                PlayerEvent::Left { name, .. } => format!("{} left", name),
+                PlayerEvent::Renamed { old_name, new_name, .. } => {
+                    format!("{} is now known as {}", old_name, new_name)
+                },
+            };
+            self.chat.add_notice(notice);
+        }
+    }
+
+    fn switch_to_next_team(&mut self) {
+        let team = match self.server_interface.connection_state() {
+            Connected { my_player_id, model, .. } => {
+                let num_teams = model.teams().len() as u64;
+                if num_teams == 0 {
+                    return;
+                }
+                match model.players().get(&my_player_id).and_then(|player| player.team()) {
+                    Some(team) => (team + 1) % num_teams,
+                    None => 0,
+                }
+            },
+            _ => return,
+        };
+        self.server_interface.switch_team(team);
+    }
 }
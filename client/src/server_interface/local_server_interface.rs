@@ -1,10 +1,21 @@
 use std::time::Instant;
 use std::thread;
+use std::mem;
 
 use shared::consts::TICK_SPEED;
+use shared::consts::DEFAULT_PLAYER_NAME;
 use shared::tick_time::TickInstant;
 use shared::model::Model;
+use shared::model::game_mode::MatchRules;
+use shared::model::movement_params::MovementParams;
+use shared::model::movement_params::MovementSettings;
 use shared::model::world::character::CharacterInput;
+use shared::model::world::PickupEvent;
+use shared::map::Map;
+use shared::chat;
+use shared::chat::ChatMessage;
+use shared::net::PlayerEvent;
+use shared::model::player;
 
 use super::HandleTrafficResult;
 use super::ConnectionState;
@@ -25,22 +36,37 @@ enum InternalState {
 }
 
 pub struct LocalServerInterface {
+    map: Map,
     internal_state: InternalState,
+    chat_messages: Vec<ChatMessage>,
+    player_events: Vec<PlayerEvent>,
+    pickup_events: Vec<PickupEvent>,
 }
 
 impl LocalServerInterface {
-    pub fn new() -> LocalServerInterface {
+    pub fn new(map: Map, player_name: String, spectator: bool) -> LocalServerInterface {
         let now = Instant::now();
-        let mut model = Model::new();
+        let mut model = Model::new(map.level().clone(), MatchRules::default());
+        // the map was validated when it was loaded
+        let movement_settings = map.movement().apply(&MovementSettings::default())
+            .expect("Map has invalid movement settings!");
+        model.set_movement_params(MovementParams::new(&movement_settings, TICK_SPEED));
+        let name = player::sanitize_name(&player_name)
+            .unwrap_or_else(|| String::from(DEFAULT_PLAYER_NAME));
+        let my_player_id = model.add_player(name.clone(), spectator);
         LocalServerInterface {
+            map,
             internal_state: Running {
                 start_tick_time: now,
-                my_player_id: model.add_player(String::from("Player")),
+                my_player_id,
                 tick: 0,
                 tick_time: now,
                 next_tick_time: now + 1 / TICK_SPEED,
                 model,
             },
+            chat_messages: Vec::new(),
+            player_events: vec![PlayerEvent::Joined { player_id: my_player_id, name }],
+            pickup_events: Vec::new(),
         }
     }
 }
@@ -68,6 +94,9 @@ impl ServerInterface for LocalServerInterface {
                 model.set_character_input(my_player_id, input);
                 for _ in 0..tick_diff {
                     model.do_tick();
+                    self.pickup_events.extend_from_slice(model.world().pickup_events());
+                    // there is no lag, so no need to rewind
+                    model.world_mut().apply_shots(|_| None);
                 }
             },
             Disconnected => (),
@@ -103,6 +132,7 @@ impl ServerInterface for LocalServerInterface {
                     ),
                     model,
                     predicted_world: model.world(),
+                    map: &self.map,
                 }
             },
             Disconnected => ConnectionState::Disconnected(DisconnectedReason::UserDisconnect),
@@ -122,6 +152,62 @@ impl ServerInterface for LocalServerInterface {
         self.internal_state = Disconnected
     }
 
+    fn switch_team(&mut self, team: u64) {
+        if let Running { my_player_id, ref mut model, .. } = self.internal_state {
+            if !model.switch_team(my_player_id, team) {
+                println!("DEBUG: May not switch to team {}!", team);
+            }
+        }
+    }
+
+    fn send_chat(&mut self, text: String, team_only: bool) {
+        if let Running { my_player_id, .. } = self.internal_state {
+            if let Some(text) = chat::sanitize_text(&text) {
+                self.chat_messages.push(ChatMessage {
+                    player_id: my_player_id,
+                    team_only,
+                    text,
+                });
+            }
+        }
+    }
+
+    fn take_chat_messages(&mut self) -> Vec<ChatMessage> {
+        mem::replace(&mut self.chat_messages, Vec::new())
+    }
+
+    fn set_spectator(&mut self, spectator: bool) {
+        if let Running { my_player_id, ref mut model, .. } = self.internal_state {
+            model.set_spectator(my_player_id, spectator);
+        }
+    }
+
+    fn rename(&mut self, name: String) {
+        if let Running { my_player_id, ref mut model, .. } = self.internal_state {
+            let name = match player::sanitize_name(&name) {
+                Some(name) => name,
+                None => return,
+            };
+            let old_name = String::from(model.player(my_player_id).unwrap().name());
+            let new_name = model.rename_player(my_player_id, name).unwrap();
+            if new_name != old_name {
+                self.player_events.push(PlayerEvent::Renamed {
+                    player_id: my_player_id,
+                    old_name,
+                    new_name,
+                });
+            }
+        }
+    }
+
+    fn take_player_events(&mut self) -> Vec<PlayerEvent> {
+        mem::replace(&mut self.player_events, Vec::new())
+    }
+
+    fn take_pickup_events(&mut self) -> Vec<PickupEvent> {
+        mem::replace(&mut self.pickup_events, Vec::new())
+    }
+
     fn do_socket_tick(&mut self) {
         // nothing
     }
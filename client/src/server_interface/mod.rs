@@ -3,48 +3,39 @@ mod remote_server_interface;
 
 use std::time::Instant;
 
-use shared::tick_time::TickInstant;
-use shared::model::Model;
-use shared::model::world::World;
+use shared::model::world::PickupEvent;
 use shared::model::world::character::CharacterInput;
+use shared::chat::ChatMessage;
+use shared::net::PlayerEvent;
 
 pub use self::local_server_interface::*;
 pub use self::remote_server_interface::*;
+pub use net_client::ConnectionState;
+pub use net_client::DisconnectedReason;
 
 pub enum HandleTrafficResult {
     Interrupt,
     Timeout,
 }
 
-#[derive(Clone, Copy)]
-pub enum DisconnectedReason<'a> {
-    NetworkError,
-    UserDisconnect,
-    Kicked {
-        kick_message: &'a str,
-    },
-    TimedOut,
-}
-
-#[derive(Clone, Copy)]
-pub enum ConnectionState<'a> {
-    Connecting,
-    Connected {
-        my_player_id: u64,
-        tick_instant: TickInstant,
-        model: &'a Model,
-        predicted_world: &'a World,
-    },
-    Disconnecting,
-    Disconnected(DisconnectedReason<'a>),
-}
-
 pub trait ServerInterface {
     fn do_tick(&mut self, input: CharacterInput);
     fn handle_traffic(&mut self, until: Instant) -> HandleTrafficResult;
     fn connection_state(&self) -> ConnectionState;
     fn next_game_tick_time(&self) -> Option<Instant>;
     fn disconnect(&mut self);
+    fn switch_team(&mut self, team: u64);
+    fn send_chat(&mut self, text: String, team_only: bool);
+    // Chat messages received since the last call.
+    fn take_chat_messages(&mut self) -> Vec<ChatMessage>;
+    // Spectators may still be waiting for a free slot, after they asked to play.
+    fn set_spectator(&mut self, spectator: bool);
+    // The server may change the name to keep it valid and unique.
+    fn rename(&mut self, name: String);
+    // Joins, leaves and renames since the last call.
+    fn take_player_events(&mut self) -> Vec<PlayerEvent>;
+    // Pickups taken in the ticks simulated since the last call, predictions included.
+    fn take_pickup_events(&mut self) -> Vec<PickupEvent>;
     fn do_socket_tick(&mut self);
     fn next_socket_tick_time(&self) -> Option<Instant>;
 }
\ No newline at end of file
@@ -1,24 +1,27 @@
-mod connected_state;
-mod socket;
-
 use std::time::Instant;
 use std::io;
+use std::mem;
 use std::net::SocketAddr;
 use std::thread;
 
 use shared::model::world::character::CharacterInput;
+use shared::model::world::PickupEvent;
+use shared::map::Map;
+use shared::chat::ChatMessage;
+use shared::net::PlayerEvent;
 use shared::net::socket::ConnectionEndReason;
+use shared::net::Transport;
 
 use super::DisconnectedReason;
 use super::ConnectionState;
 use super::ServerInterface;
 use super::HandleTrafficResult;
-use self::connected_state::ConnectedState;
-use self::connected_state::ConnectedStateTickResult;
+use net_client::connected_state::ConnectedState;
+use net_client::connected_state::ConnectedStateTickResult;
+use net_client::socket::ClientSocket;
+use net_client::socket::ClientSocketEvent;
 use self::InternalState::*;
 use self::InternalDisconnectedReason::*;
-use self::socket::ClientSocket;
-use self::socket::ClientSocketEvent;
 
 enum InternalDisconnectedReason {
     NetworkError(io::Error),
@@ -27,6 +30,7 @@ enum InternalDisconnectedReason {
         kick_message: String,
     },
     TimedOut,
+    MapUnavailable,
 }
 
 #[derive(Clone, Copy)]
@@ -34,6 +38,7 @@ enum DisconnectingReason {
     UserDisconnect,
     SnapshotTimeout,
     InputAckTimeout,
+    MapUnavailable,
 }
 
 enum InternalState {
@@ -46,13 +51,18 @@ enum InternalState {
 pub struct RemoteServerInterface {
     internal_state: InternalState,
     socket: ClientSocket,
+    chat_messages: Vec<ChatMessage>,
+    player_events: Vec<PlayerEvent>,
 }
 
 impl RemoteServerInterface {
-    pub fn new(addr: SocketAddr) -> io::Result<RemoteServerInterface> {
+    pub fn new(addr: SocketAddr, transport: Transport, player_name: String, spectator: bool)
+            -> io::Result<RemoteServerInterface> {
         Ok(RemoteServerInterface {
-            socket: ClientSocket::new(addr)?,
+            socket: ClientSocket::new(addr, transport, player_name, spectator)?,
             internal_state: Connecting,
+            chat_messages: Vec::new(),
+            player_events: Vec::new(),
         })
     }
 }
@@ -89,9 +99,37 @@ impl ServerInterface for RemoteServerInterface {
             return HandleTrafficResult::Timeout;
         }
         match self.socket.wait_event(until) {
-            Some(ClientSocketEvent::DoneConnecting { my_player_id }) => {
+            Some(ClientSocketEvent::DoneConnecting {
+                my_player_id,
+                map_name,
+                map_hash,
+                movement_params,
+            }) => {
                 if let Connecting = self.internal_state {
-                    self.internal_state = Connected(ConnectedState::new(my_player_id));
+                    match Map::load(&map_name) {
+                        Ok(ref map) if map.hash() != map_hash => {
+                            println!(
+                                "ERROR: Map \"{}\" differs from the server's version!",
+                                map_name,
+                            );
+                            self.socket.disconnect();
+                            self.internal_state = Disconnecting(
+                                DisconnectingReason::MapUnavailable
+                            );
+                        },
+                        Ok(map) => {
+                            self.internal_state = Connected(
+                                ConnectedState::new(my_player_id, map, movement_params)
+                            );
+                        },
+                        Err(err) => {
+                            println!("ERROR: Could not load map \"{}\": {}", map_name, err);
+                            self.socket.disconnect();
+                            self.internal_state = Disconnecting(
+                                DisconnectingReason::MapUnavailable
+                            );
+                        },
+                    }
                 } else {
                     panic!("Got DoneConnecting event while not connecting!");
                 }
@@ -105,6 +143,22 @@ impl ServerInterface for RemoteServerInterface {
                 }
                 HandleTrafficResult::Interrupt
             },
+            Some(ClientSocketEvent::ChatReceived(msg)) => {
+                if let Connected(_) = self.internal_state {
+                    self.chat_messages.push(msg);
+                } else {
+                    panic!("Got ChatReceived event while not connected!");
+                }
+                HandleTrafficResult::Interrupt
+            },
+            Some(ClientSocketEvent::PlayerEventReceived(event)) => {
+                if let Connected(_) = self.internal_state {
+                    self.player_events.push(event);
+                } else {
+                    panic!("Got PlayerEventReceived event while not connected!");
+                }
+                HandleTrafficResult::Interrupt
+            },
             Some(ClientSocketEvent::InputAckReceived { input_tick, arrival_tick_instant }) => {
                 if let Connected(ref mut con_state) = self.internal_state {
                     con_state.on_input_ack(input_tick, arrival_tick_instant);
@@ -113,6 +167,8 @@ impl ServerInterface for RemoteServerInterface {
                 }
                 HandleTrafficResult::Interrupt
             },
+            // only load tests look at the server stats
+            Some(ClientSocketEvent::StatsReceived(_)) => HandleTrafficResult::Interrupt,
             Some(ClientSocketEvent::ConnectionClosed) => {
                 if let Connected(_) = self.internal_state {
                     self.internal_state = Disconnected(Kicked {
@@ -139,6 +195,9 @@ impl ServerInterface for RemoteServerInterface {
                             println!("DEBUG: Timed out!");
                             self.internal_state = Disconnected(TimedOut);
                         },
+                        DisconnectingReason::MapUnavailable => {
+                            self.internal_state = Disconnected(MapUnavailable);
+                        },
                     }
                 } else {
                     panic!("Got DoneDisconnecting event while not disconnecting!");
@@ -154,6 +213,9 @@ impl ServerInterface for RemoteServerInterface {
                         ConnectionEndReason::Reset => {
                             println!("DEBUG: Connection reset!");
                         },
+                        ConnectionEndReason::Banned => {
+                            println!("DEBUG: Banned by server!");
+                        },
                     }
                     // TODO inform about unsent messages
                     self.internal_state = Disconnected(TimedOut);
@@ -174,6 +236,9 @@ impl ServerInterface for RemoteServerInterface {
                                 ConnectionEndReason::Reset => {
                                     println!("DEBUG: Connection reset during disconnect!");
                                 },
+                                ConnectionEndReason::Banned => {
+                                    println!("DEBUG: Banned by server during disconnect!");
+                                },
                             }
                             self.internal_state = Disconnected(UserDisconnect);
                         },
@@ -183,6 +248,9 @@ impl ServerInterface for RemoteServerInterface {
                         DisconnectingReason::InputAckTimeout => {
                             self.internal_state = Disconnected(TimedOut);
                         },
+                        DisconnectingReason::MapUnavailable => {
+                            self.internal_state = Disconnected(MapUnavailable);
+                        },
                     }
                 } else {
                     panic!("Got DisconnectingConnectionEnd event while not disconnecting!");
@@ -212,6 +280,7 @@ impl ServerInterface for RemoteServerInterface {
                 &Kicked { ref kick_message } => DisconnectedReason::Kicked { kick_message },
                 &TimedOut => DisconnectedReason::TimedOut,
                 &NetworkError(_) => DisconnectedReason::NetworkError,
+                &MapUnavailable => DisconnectedReason::MapUnavailable,
             }),
         }
     }
@@ -233,6 +302,45 @@ impl ServerInterface for RemoteServerInterface {
         }
     }
 
+    fn switch_team(&mut self, team: u64) {
+        if let Connected(_) = self.internal_state {
+            self.socket.send_switch_team(team);
+        }
+    }
+
+    fn send_chat(&mut self, text: String, team_only: bool) {
+        if let Connected(_) = self.internal_state {
+            self.socket.send_chat(text, team_only);
+        }
+    }
+
+    fn take_chat_messages(&mut self) -> Vec<ChatMessage> {
+        mem::replace(&mut self.chat_messages, Vec::new())
+    }
+
+    fn set_spectator(&mut self, spectator: bool) {
+        if let Connected(_) = self.internal_state {
+            self.socket.send_set_spectator(spectator);
+        }
+    }
+
+    fn rename(&mut self, name: String) {
+        if let Connected(_) = self.internal_state {
+            self.socket.send_rename(name);
+        }
+    }
+
+    fn take_player_events(&mut self) -> Vec<PlayerEvent> {
+        mem::replace(&mut self.player_events, Vec::new())
+    }
+
+    fn take_pickup_events(&mut self) -> Vec<PickupEvent> {
+        match self.internal_state {
+            Connected(ref mut state) => state.take_pickup_events(),
+            _ => Vec::new(),
+        }
+    }
+
     fn do_socket_tick(&mut self) {
         self.socket.do_tick();
     }
@@ -0,0 +1,100 @@
+use cgmath::Vector3;
+
+use shared::consts::TICK_SPEED;
+use shared::consts::SPECTATOR_CAMERA_SPEED;
+use shared::model::Model;
+use shared::model::world::character::CharacterInput;
+use shared::model::world::character::ViewDir;
+use shared::model::world::entity::EntityId;
+
+// Where spectators look from, either through the eyes of a player or flying freely.
+pub struct SpectatorCamera {
+    // the followed player, flying freely if None
+    target: Option<u64>,
+    pos: Vector3<f32>,
+    view_dir: ViewDir,
+}
+
+impl SpectatorCamera {
+    pub fn new() -> SpectatorCamera {
+        SpectatorCamera {
+            target: None,
+            // a bit above the origin, so the level is in view
+            pos: Vector3::new(0.0, 0.0, 5.0),
+            view_dir: Default::default(),
+        }
+    }
+
+    pub fn next_target(&mut self, model: &Model) {
+        self.cycle_target(model, true);
+    }
+
+    pub fn prev_target(&mut self, model: &Model) {
+        self.cycle_target(model, false);
+    }
+
+    // Free flight sits between the last and the first playing player.
+    fn cycle_target(&mut self, model: &Model, forward: bool) {
+        let mut player_ids: Vec<u64> = model.players().iter()
+            .filter(|&(_, player)| player.playing())
+            .map(|(&player_id, _)| player_id)
+            .collect();
+        player_ids.sort();
+        let current_index = self.target
+            .and_then(|target| player_ids.iter().position(|&player_id| player_id == target));
+        let next_index = match (current_index, forward) {
+            (None, true) if !player_ids.is_empty() => Some(0),
+            (None, true) => None,
+            (None, false) => player_ids.len().checked_sub(1),
+            (Some(index), true) if index + 1 < player_ids.len() => Some(index + 1),
+            (Some(_), true) => None,
+            (Some(index), false) => index.checked_sub(1),
+        };
+        // continue flying from where the followed player was
+        if let Some(character) = self.target_character_id(model)
+                .and_then(|id| model.world().character(id)) {
+            self.pos = character.pos().into();
+        }
+        self.target = next_index.map(|index| player_ids[index]);
+    }
+
+    pub fn target_character_id(&self, model: &Model) -> Option<EntityId> {
+        self.target
+            .and_then(|player_id| model.player(player_id))
+            .and_then(|player| player.character_id())
+    }
+
+    // Moves the free camera with the movement keys, called once per tick.
+    pub fn fly(&mut self, input: &CharacterInput) {
+        self.view_dir = input.view_dir;
+        if self.target.is_some() {
+            return;
+        }
+        let yaw = input.view_dir.yaw().rad_f32();
+        let forward: Vector3<f32> = input.view_dir.direction().into();
+        let right = Vector3::new(yaw.sin(), -yaw.cos(), 0.0);
+        let mut dir = Vector3::new(0.0, 0.0, 0.0);
+        if input.forward {
+            dir += forward;
+        }
+        if input.backward {
+            dir -= forward;
+        }
+        if input.right {
+            dir += right;
+        }
+        if input.left {
+            dir -= right;
+        }
+        let step = (SPECTATOR_CAMERA_SPEED / TICK_SPEED.per_second() as f64) as f32;
+        self.pos += dir * step;
+    }
+
+    pub fn pos(&self) -> Vector3<f32> {
+        self.pos
+    }
+
+    pub fn view_dir(&self) -> ViewDir {
+        self.view_dir
+    }
+}
\ No newline at end of file
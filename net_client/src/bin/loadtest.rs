@@ -0,0 +1,13 @@
+extern crate net_client;
+
+use std::env;
+
+use net_client::loadtest;
+use net_client::loadtest::LoadTestConfig;
+
+fn main() {
+    match LoadTestConfig::from_args(env::args().skip(1)) {
+        Ok(config) => loadtest::run(&config),
+        Err(err) => println!("{}", err),
+    }
+}
\ No newline at end of file
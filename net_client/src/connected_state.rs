@@ -1,11 +1,19 @@
 use std::time::Instant;
 use std::collections::HashMap;
 use std::iter;
+use std::mem;
+use std::sync::Arc;
 
 use shared::tick_time::TickInstant;
 use shared::tick_time::TickRate;
+use shared::math::Vec3;
 use shared::model::Model;
+use shared::model::game_mode::MatchRules;
 use shared::model::world::World;
+use shared::model::world::PickupEvent;
+use shared::model::movement_params::MovementParams;
+use shared::model::world::level::Level;
+use shared::map::Map;
 use shared::model::world::character::CharacterInput;
 use shared::net::Snapshot;
 use shared::consts;
@@ -17,8 +25,8 @@ use shared::consts::INPUT_ARRIVAL_SIGMA_FACTOR;
 use shared::util;
 use shared::online_distribution::OnlineDistribution;
 
-use server_interface::remote_server_interface::socket::ClientSocket;
-use server_interface::ConnectionState;
+use socket::ClientSocket;
+use ConnectionState;
 
 use self::InternalState::*;
 
@@ -44,23 +52,38 @@ struct AfterSnapshotData {
     sent_input_times: HashMap<u64, Instant>,
     last_valid_snapshot_time: Instant,
     last_valid_input_ack_time: Instant,
+    // only the first desync is reported, the following ones are usually caused by it,
+    // except for the first one that tells which entity diverged
+    desync_tick: Option<u64>,
+    diverged_entity_reported: bool,
+    // the model is simulated again every tick, events are only kept the first time
+    pickup_events: Vec<PickupEvent>,
+    last_event_tick: u64,
+    // where we predicted our character, to compare with the snapshots
+    predicted_positions: HashMap<u64, Vec3>,
+    prediction_corrections: u64,
 }
 
 impl AfterSnapshotData {
-    fn new(snapshot: Snapshot) -> AfterSnapshotData {
+    fn new(snapshot: Snapshot, level: &Arc<Level>, movement_params: &MovementParams)
+    -> AfterSnapshotData {
         let recv_time = Instant::now();
         let start_tick_time = recv_time - snapshot.tick() / TICK_SPEED;
         let start_predicted_tick_time = start_tick_time - consts::initial_lag_assumption();
+        let oldest_snapshot_tick = snapshot.tick();
+        let mut predicted_world = World::new(level.clone()); // maybe don't initialize this yet
+        predicted_world.set_movement_params(*movement_params);
         AfterSnapshotData {
             tick: snapshot.tick(),
             predicted_tick: snapshot.tick(),
             predicted_tick_decrease: 0.0,
             tick_time: recv_time - 1 / TICK_SPEED,
             next_tick_time: recv_time,
-            model: Model::new(), // maybe don't initialize this yet
-            predicted_world: World::new(), // maybe don't initialize this yet
+            // maybe don't initialize this yet
+            model: Model::new(level.clone(), MatchRules::default()),
+            predicted_world,
             start_tick_time_distribution: OnlineDistribution::new(start_tick_time),
-            oldest_snapshot_tick: snapshot.tick(),
+            oldest_snapshot_tick,
             snapshots: iter::once((snapshot.tick(), snapshot)).collect(),
             // TODO start_predicted_tick_time should be determined by ping from connection request instead
             start_predicted_tick_distribution: OnlineDistribution::new(start_predicted_tick_time),
@@ -68,10 +91,17 @@ impl AfterSnapshotData {
             sent_input_times: HashMap::new(),
             last_valid_snapshot_time: recv_time,
             last_valid_input_ack_time: recv_time,
+            desync_tick: None,
+            diverged_entity_reported: false,
+            pickup_events: Vec::new(),
+            last_event_tick: oldest_snapshot_tick,
+            predicted_positions: HashMap::new(),
+            prediction_corrections: 0,
         }
     }
 
-    pub fn on_snapshot(&mut self, snapshot: Snapshot) {
+    pub fn on_snapshot(&mut self, snapshot: Snapshot, level: &Arc<Level>,
+                       movement_params: &MovementParams) {
         let recv_time = Instant::now();
         let start_tick_time = recv_time - snapshot.tick() / TICK_SPEED;
         if false {
@@ -94,13 +124,77 @@ impl AfterSnapshotData {
         );
         // TODO ignore snapshots with insanely high tick
         if snapshot.tick() > self.oldest_snapshot_tick {
-            self.snapshots.insert(snapshot.tick(), snapshot);
+            let tick = snapshot.tick();
+            self.snapshots.insert(tick, snapshot);
             self.last_valid_snapshot_time = recv_time;
+            // snapshots may arrive out of order
+            self.check_desync(tick, level, movement_params);
+            self.check_desync(tick + 1, level, movement_params);
         } else {
             println!("DEBUG: Discarded snapshot {}!", snapshot.tick());
         }
     }
 
+    // Simulates the tick of a snapshot from the one before and compares the result
+    // with the checksums of the server.
+    // Ticks in which the server changed the model outside of the simulation are skipped.
+    fn check_desync(&mut self, tick: u64, level: &Arc<Level>, movement_params: &MovementParams) {
+        if self.diverged_entity_reported || tick == 0 {
+            return;
+        }
+        let previous = match self.snapshots.get(&(tick - 1)) {
+            Some(previous) => previous,
+            None => return,
+        };
+        let current = match self.snapshots.get(&tick) {
+            Some(current) => current,
+            None => return,
+        };
+        let checksums = current.checksums();
+        if self.desync_tick.is_some() && checksums.entities.is_none() {
+            return;
+        }
+        let mut model = previous.model().clone();
+        model.set_level(level.clone());
+        model.set_movement_params(*movement_params);
+        for (character_id, character) in current.model().world().entities().characters() {
+            model.world_mut().set_character_input(character_id, character.input());
+        }
+        if model.checksum() != checksums.before_tick {
+            return;
+        }
+        model.do_tick();
+        if model.checksum() == checksums.after_tick {
+            return;
+        }
+        let first_desync = self.desync_tick.is_none();
+        self.desync_tick = self.desync_tick.or(Some(tick));
+        let their_entity_checksums = match checksums.entities {
+            Some(ref entities) => entities,
+            None => {
+                if first_desync {
+                    println!("ERROR: Desync in tick {}!", tick);
+                }
+                return;
+            },
+        };
+        self.diverged_entity_reported = true;
+        let entity_checksums = model.world().entity_checksums();
+        let diverged = entity_checksums.iter().zip(their_entity_checksums.iter())
+            .find(|&(mine, theirs)| mine != theirs)
+            .map(|(&(id, _), _)| id);
+        match diverged {
+            Some(id) => println!("ERROR: Desync in tick {}, entity {} diverged first!", tick, id),
+            None if entity_checksums.len() != their_entity_checksums.len() => println!(
+                "ERROR: Desync in tick {}, {} entities instead of {}!",
+                tick,
+                entity_checksums.len(),
+                their_entity_checksums.len(),
+            ),
+            None => println!("ERROR: Desync in tick {} outside of the entities!", tick),
+        }
+    }
+
     fn on_input_ack(&mut self, input_tick: u64, arrival_tick_instant: TickInstant) {
         if let Some(send_time) = self.sent_input_times.get(&input_tick) {
             let start_predicted_tick_time = *send_time
@@ -192,7 +286,8 @@ impl AfterSnapshotData {
         }
 
         // TODO if we resend any input, the server will send another ack and we might calculate a wrong input delay
-        socket.send_input(self.predicted_tick, character_input);
+        // the server checks our shots against the model we are showing
+        socket.send_input(self.predicted_tick, character_input, self.tick);
         self.sent_input_times.insert(self.predicted_tick, send_time);
         self.sent_inputs.insert(self.predicted_tick, character_input);
     }
@@ -222,9 +317,24 @@ impl AfterSnapshotData {
         self.sent_input_times.retain(|_, time| now - *time < consts::max_input_keep_time() )
     }
 
-    fn update_model(&mut self, my_player_id: u64) {
+    fn update_model(&mut self, my_player_id: u64, level: &Arc<Level>,
+                    movement_params: &MovementParams) {
         let oldest_snapshot = self.snapshots.get(&self.oldest_snapshot_tick).unwrap();
         self.model = oldest_snapshot.model().clone(); // TODO do this better
+        self.model.set_level(level.clone());
+        self.model.set_movement_params(*movement_params);
+        let predicted_pos = self.predicted_positions.remove(&self.oldest_snapshot_tick);
+        let snapshot_pos = self.model.player(my_player_id)
+            .and_then(|p| p.character_id())
+            .and_then(|character_id| self.model.world().character(character_id))
+            .map(|character| character.pos());
+        if let (Some(predicted_pos), Some(snapshot_pos)) = (predicted_pos, snapshot_pos) {
+            if predicted_pos != snapshot_pos {
+                self.prediction_corrections += 1;
+            }
+        }
+        let oldest_snapshot_tick = self.oldest_snapshot_tick;
+        self.predicted_positions.retain(|&tick, _| tick > oldest_snapshot_tick);
         let tick_diff = self.tick - self.oldest_snapshot_tick;
         if tick_diff > 0 {
             println!(
@@ -235,6 +345,9 @@ impl AfterSnapshotData {
                 self.oldest_snapshot_tick
             );
         }
+        if self.oldest_snapshot_tick > self.last_event_tick {
+            self.pickup_events.extend_from_slice(self.model.world().pickup_events());
+        }
         for tick in (self.oldest_snapshot_tick + 1)..(self.tick + 1) {
             if let Some(input) = self.sent_inputs.get(&tick) {
                 if self.model.player(my_player_id).is_some() {
@@ -244,14 +357,27 @@ impl AfterSnapshotData {
                 }
             }
             self.model.do_tick();
+            // only the server can rewind, so we predict hits against the world we see
+            self.model.world_mut().apply_shots(|_| None);
+            if tick > self.last_event_tick {
+                self.pickup_events.extend_from_slice(self.model.world().pickup_events());
+            }
         }
+        self.last_event_tick = self.last_event_tick.max(self.tick);
 
         self.predicted_world = self.model.world().clone();
+        let my_character_id = self.model.player(my_player_id).and_then(|p| p.character_id());
         for tick in (self.tick + 1)..(self.predicted_tick + 1) {
-            if let Some(&input) = self.sent_inputs.get(&tick) {
-                self.predicted_world.set_character_input(my_player_id, input);
+            if let (Some(&input), Some(character_id)) =
+                    (self.sent_inputs.get(&tick), my_character_id) {
+                self.predicted_world.set_character_input(character_id, input);
             }
             self.predicted_world.do_tick();
+            self.predicted_world.apply_shots(|_| None);
+        }
+        if let Some(character) = my_character_id
+            .and_then(|character_id| self.predicted_world.character(character_id)) {
+            self.predicted_positions.insert(self.predicted_tick, character.pos());
         }
     }
 }
@@ -265,13 +391,17 @@ enum InternalState {
 
 pub struct ConnectedState {
     my_player_id: u64,
+    map: Map,
+    movement_params: MovementParams,
     internal_state: InternalState,
 }
 
 impl ConnectedState {
-    pub fn new(my_player_id: u64) -> ConnectedState {
+    pub fn new(my_player_id: u64, map: Map, movement_params: MovementParams) -> ConnectedState {
         ConnectedState {
             my_player_id,
+            map,
+            movement_params,
             internal_state: BeforeSnapshot { init_time: Instant::now() },
         }
     }
@@ -297,7 +427,7 @@ impl ConnectedState {
                 data.update_tick();
                 data.send_and_save_input(character_input, socket);
                 data.remove_old_snapshots_and_inputs();
-                data.update_model( self.my_player_id);
+                data.update_model(self.my_player_id, self.map.level(), &self.movement_params);
                 ConnectedStateTickResult::Ok
             }
         }
@@ -322,6 +452,7 @@ impl ConnectedState {
                 ),
                 model: &data.model,
                 predicted_world: &data.predicted_world,
+                map: &self.map,
             }
         }
     }
@@ -329,9 +460,20 @@ impl ConnectedState {
     pub fn on_snapshot(&mut self, snapshot: Snapshot) {
         match self.internal_state {
             BeforeSnapshot { .. } => {
-                self.internal_state = AfterSnapshot(AfterSnapshotData::new(snapshot))
+                self.internal_state = AfterSnapshot(
+                    AfterSnapshotData::new(snapshot, self.map.level(), &self.movement_params)
+                )
             },
-            AfterSnapshot(ref mut data) => data.on_snapshot(snapshot),
+            AfterSnapshot(ref mut data) => {
+                data.on_snapshot(snapshot, self.map.level(), &self.movement_params)
+            },
+        }
+    }
+
+    pub fn take_pickup_events(&mut self) -> Vec<PickupEvent> {
+        match self.internal_state {
+            BeforeSnapshot { .. } => Vec::new(),
+            AfterSnapshot(ref mut data) => mem::replace(&mut data.pickup_events, Vec::new()),
         }
     }
 
@@ -340,4 +482,12 @@ impl ConnectedState {
             data.on_input_ack(input_tick, arrival_tick_instant);
         }
     }
+
+    // How often a snapshot put our character somewhere else than we predicted.
+    pub fn prediction_corrections(&self) -> u64 {
+        match self.internal_state {
+            BeforeSnapshot { .. } => 0,
+            AfterSnapshot(ref data) => data.prediction_corrections,
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,38 @@
+extern crate rand;
+extern crate arrayvec;
+
+extern crate shared;
+
+pub mod socket;
+pub mod connected_state;
+pub mod loadtest;
+
+use shared::tick_time::TickInstant;
+use shared::model::Model;
+use shared::model::world::World;
+use shared::map::Map;
+
+#[derive(Clone, Copy)]
+pub enum DisconnectedReason<'a> {
+    NetworkError,
+    UserDisconnect,
+    Kicked {
+        kick_message: &'a str,
+    },
+    TimedOut,
+    MapUnavailable,
+}
+
+#[derive(Clone, Copy)]
+pub enum ConnectionState<'a> {
+    Connecting,
+    Connected {
+        my_player_id: u64,
+        tick_instant: TickInstant,
+        model: &'a Model,
+        predicted_world: &'a World,
+        map: &'a Map,
+    },
+    Disconnecting,
+    Disconnected(DisconnectedReason<'a>),
+}
\ No newline at end of file
@@ -0,0 +1,463 @@
+use std::net::SocketAddr;
+use std::net::ToSocketAddrs;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use rand;
+use rand::Rng;
+use rand::XorShiftRng;
+
+use shared::consts::TICK_SPEED;
+use shared::map::Map;
+use shared::math::FPAngle;
+use shared::model::world::character::CharacterInput;
+use shared::model::world::character::WEAPONS;
+use shared::net::Transport;
+use shared::net::ServerStats;
+use shared::net::socket::ConnectionEndReason;
+use shared::util;
+
+use connected_state::ConnectedState;
+use connected_state::ConnectedStateTickResult;
+use socket::ClientSocket;
+use socket::ClientSocketEvent;
+
+const USAGE: &'static str =
+    "Usage: loadtest <address> [clients] [seconds] [idle|scripted|random] [udp|tcp]";
+
+// spreads the connection requests, so they don't all arrive in the same tick
+const CONNECT_INTERVAL_MILLIS: u64 = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputMode {
+    Idle,
+    // walks in circles, jumps and fires in a fixed rhythm
+    Scripted,
+    Random,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LoadTestConfig {
+    pub addr: SocketAddr,
+    pub transport: Transport,
+    pub clients: u64,
+    pub duration: Duration,
+    pub input_mode: InputMode,
+}
+
+impl LoadTestConfig {
+    // The arguments without the program name.
+    pub fn from_args<I: Iterator<Item = String>>(mut args: I) -> Result<LoadTestConfig, String> {
+        let addr = args.next()
+            .and_then(|addr| addr.to_socket_addrs().ok())
+            .and_then(|mut addrs| addrs.next())
+            .ok_or_else(|| String::from(USAGE))?;
+        let mut config = LoadTestConfig {
+            addr,
+            transport: Transport::Udp,
+            clients: 10,
+            duration: Duration::from_secs(30),
+            input_mode: InputMode::Random,
+        };
+        if let Some(clients) = args.next() {
+            config.clients = clients.parse().map_err(|_| String::from(USAGE))?;
+        }
+        if let Some(seconds) = args.next() {
+            let seconds = seconds.parse().map_err(|_| String::from(USAGE))?;
+            config.duration = Duration::from_secs(seconds);
+        }
+        if let Some(input_mode) = args.next() {
+            config.input_mode = match input_mode.as_str() {
+                "idle" => InputMode::Idle,
+                "scripted" => InputMode::Scripted,
+                "random" => InputMode::Random,
+                _ => return Err(String::from(USAGE)),
+            };
+        }
+        if let Some(transport) = args.next() {
+            config.transport = match transport.as_str() {
+                "udp" => Transport::Udp,
+                "tcp" => Transport::Tcp,
+                _ => return Err(String::from(USAGE)),
+            };
+        }
+        Ok(config)
+    }
+}
+
+// What a single simulated client experienced.
+#[derive(Debug, Default)]
+struct ClientReport {
+    connect_duration: Option<Duration>,
+    // why the client stopped before the end of the test
+    failure: Option<String>,
+    ticks: u64,
+    snapshots: u64,
+    // in seconds, how much later than expected by the tick rate the snapshots arrived
+    snapshot_delay_sum: f64,
+    snapshot_delay_square_sum: f64,
+    prediction_corrections: u64,
+    input_acks: u64,
+    // inputs that arrived after their tick was simulated
+    late_inputs: u64,
+    // in ticks, how early the inputs arrived
+    input_lead_sum: f64,
+    // the lowest tick rate the server reported
+    min_server_tick_rate: Option<u64>,
+    max_server_clients: u64,
+    last_server_stats: Option<ServerStats>,
+}
+
+impl ClientReport {
+    fn snapshot_jitter(&self) -> Option<f64> {
+        if self.snapshots < 2 {
+            return None;
+        }
+        let n = self.snapshots as f64;
+        let mean = self.snapshot_delay_sum / n;
+        Some((self.snapshot_delay_square_sum / n - mean * mean).max(0.0).sqrt())
+    }
+}
+
+// Connects simulated clients without graphics, to find out how many players a server handles.
+pub fn run(config: &LoadTestConfig) {
+    println!(
+        "Connecting {} clients to {} for {} seconds...",
+        config.clients,
+        config.addr,
+        config.duration.as_secs(),
+    );
+    let mut threads = Vec::new();
+    for index in 0..config.clients {
+        let config = *config;
+        let delay = Duration::from_millis(index * CONNECT_INTERVAL_MILLIS);
+        threads.push(thread::spawn(move || {
+            thread::sleep(delay);
+            SimulatedClient::new(index, &config).run()
+        }));
+    }
+    let reports: Vec<ClientReport> = threads.into_iter()
+        .map(|thread| thread.join().unwrap_or_else(|_| ClientReport {
+            failure: Some(String::from("client thread panicked")),
+            ..ClientReport::default()
+        }))
+        .collect();
+    print_summary(config, &reports);
+}
+
+fn print_summary(config: &LoadTestConfig, reports: &[ClientReport]) {
+    let connected: Vec<&ClientReport> = reports.iter()
+        .filter(|report| report.connect_duration.is_some())
+        .collect();
+    println!("Connected: {}/{}", connected.len(), reports.len());
+    for (index, report) in reports.iter().enumerate() {
+        if let Some(ref failure) = report.failure {
+            println!("  client {}: {}", index + 1, failure);
+        }
+    }
+    if connected.is_empty() {
+        return;
+    }
+    let seconds = util::duration_as_float(config.duration);
+    println!(
+        "Connect time: {:.1}ms mean",
+        mean(&connected, |r| util::duration_as_float(r.connect_duration.unwrap())) * 1000.0,
+    );
+    println!(
+        "Ticks/s: {:.1}, snapshots/s: {:.1} per client",
+        mean(&connected, |r| r.ticks as f64) / seconds,
+        mean(&connected, |r| r.snapshots as f64) / seconds,
+    );
+    let jitters: Vec<f64> = connected.iter().filter_map(|r| r.snapshot_jitter()).collect();
+    if !jitters.is_empty() {
+        println!(
+            "Snapshot jitter: {:.2}ms mean, {:.2}ms max",
+            jitters.iter().sum::<f64>() / jitters.len() as f64 * 1000.0,
+            jitters.iter().cloned().fold(0.0, f64::max) * 1000.0,
+        );
+    }
+    println!(
+        "Prediction corrections: {:.1}/s per client",
+        mean(&connected, |r| r.prediction_corrections as f64) / seconds,
+    );
+    let input_acks: u64 = connected.iter().map(|r| r.input_acks).sum();
+    let late_inputs: u64 = connected.iter().map(|r| r.late_inputs).sum();
+    if input_acks > 0 {
+        println!(
+            "Input acks: {}, late inputs: {} ({:.2}%), mean input lead: {:.2} ticks",
+            input_acks,
+            late_inputs,
+            late_inputs as f64 / input_acks as f64 * 100.0,
+            connected.iter().map(|r| r.input_lead_sum).sum::<f64>() / input_acks as f64,
+        );
+    }
+    let server_stats: Vec<ServerStats> = connected.iter()
+        .filter_map(|r| r.last_server_stats)
+        .collect();
+    if server_stats.is_empty() {
+        println!("The server reported no stats");
+        return;
+    }
+    println!(
+        "Server ticks/s: {} min, clients: {} max",
+        connected.iter().filter_map(|r| r.min_server_tick_rate).min().unwrap(),
+        connected.iter().map(|r| r.max_server_clients).max().unwrap(),
+    );
+    // the counters only grow, so the highest values are the newest
+    println!(
+        "Server rate limited packets: {}, banned packets: {}, bans: {}, \
+         input violations: {}, kicks: {}",
+        server_stats.iter().map(|s| s.rate_limited_packets).max().unwrap(),
+        server_stats.iter().map(|s| s.banned_packets).max().unwrap(),
+        server_stats.iter().map(|s| s.bans).max().unwrap(),
+        server_stats.iter().map(|s| s.input_violations).max().unwrap(),
+        server_stats.iter().map(|s| s.kicks).max().unwrap(),
+    );
+}
+
+fn mean<F: Fn(&ClientReport) -> f64>(reports: &[&ClientReport], value: F) -> f64 {
+    reports.iter().map(|report| value(report)).sum::<f64>() / reports.len() as f64
+}
+
+enum SimulatedClientState {
+    Connecting,
+    Connected(ConnectedState),
+    Disconnecting,
+    Disconnected,
+}
+
+struct SimulatedClient {
+    index: u64,
+    config: LoadTestConfig,
+    state: SimulatedClientState,
+    input: CharacterInput,
+    rng: XorShiftRng,
+    report: ClientReport,
+    // the arrival of the first snapshot, the others are measured against it
+    first_snapshot: Option<(u64, Instant)>,
+}
+
+impl SimulatedClient {
+    fn new(index: u64, config: &LoadTestConfig) -> SimulatedClient {
+        SimulatedClient {
+            index,
+            config: *config,
+            state: SimulatedClientState::Connecting,
+            input: CharacterInput::default(),
+            rng: rand::weak_rng(),
+            report: ClientReport::default(),
+            first_snapshot: None,
+        }
+    }
+
+    fn run(mut self) -> ClientReport {
+        let name = format!("loadtest {}", self.index + 1);
+        let socket = ClientSocket::new(self.config.addr, self.config.transport, name, false);
+        let mut socket = match socket {
+            Ok(socket) => socket,
+            Err(err) => {
+                self.report.failure = Some(format!("could not open socket: {}", err));
+                return self.report;
+            },
+        };
+        let start_time = Instant::now();
+        let end_time = start_time + self.config.duration;
+        loop {
+            if let SimulatedClientState::Disconnected = self.state {
+                break;
+            }
+            let now = Instant::now();
+            if now >= end_time {
+                match self.state {
+                    SimulatedClientState::Connecting | SimulatedClientState::Connected(_) => {
+                        if self.report.connect_duration.is_none() {
+                            self.report.failure = Some(String::from("never connected"));
+                        }
+                        socket.disconnect();
+                        self.state = SimulatedClientState::Disconnecting;
+                    },
+                    SimulatedClientState::Disconnecting
+                    | SimulatedClientState::Disconnected => (),
+                }
+            }
+
+            // sleep until the next tick or event
+            let next_game_tick_time = match self.state {
+                SimulatedClientState::Connected(ref state) => state.next_tick_time(),
+                _ => None,
+            };
+            let next_socket_tick_time = socket.next_tick_time();
+            let mut next_time = Instant::now() + Duration::from_millis(100);
+            for time in next_game_tick_time.iter().chain(next_socket_tick_time.iter()) {
+                next_time = next_time.min(*time);
+            }
+            if let SimulatedClientState::Connecting | SimulatedClientState::Connected(_)
+                    = self.state {
+                next_time = next_time.min(end_time);
+            }
+            if let Some(event) = socket.wait_event(next_time) {
+                if let Err(failure) = self.handle_event(event, start_time, &mut socket) {
+                    self.report.failure = Some(failure);
+                }
+                continue;
+            }
+
+            // tick
+            let now = Instant::now();
+            if next_socket_tick_time.map_or(false, |time| time <= now) {
+                socket.do_tick();
+            }
+            if next_game_tick_time.map_or(false, |time| time <= now) {
+                self.update_input();
+                let result = match self.state {
+                    SimulatedClientState::Connected(ref mut state) => {
+                        let result = state.do_tick(self.input, &mut socket);
+                        self.report.prediction_corrections = state.prediction_corrections();
+                        result
+                    },
+                    _ => ConnectedStateTickResult::Ok,
+                };
+                self.report.ticks += 1;
+                match result {
+                    ConnectedStateTickResult::Ok => (),
+                    ConnectedStateTickResult::SnapshotTimeout => {
+                        self.report.failure = Some(String::from("snapshot timeout"));
+                        socket.disconnect();
+                        self.state = SimulatedClientState::Disconnecting;
+                    },
+                    ConnectedStateTickResult::InputAckTimeout => {
+                        self.report.failure = Some(String::from("input ack timeout"));
+                        socket.disconnect();
+                        self.state = SimulatedClientState::Disconnecting;
+                    },
+                }
+            }
+        }
+        self.report
+    }
+
+    // Errors end the test for this client.
+    fn handle_event(&mut self, event: ClientSocketEvent, start_time: Instant,
+                    socket: &mut ClientSocket) -> Result<(), String> {
+        match event {
+            ClientSocketEvent::DoneConnecting {
+                my_player_id,
+                map_name,
+                map_hash,
+                movement_params,
+            } => {
+                let map = match Map::load(&map_name) {
+                    Ok(ref map) if map.hash() != map_hash => Err(format!(
+                        "map \"{}\" differs from the server's version",
+                        map_name,
+                    )),
+                    Ok(map) => Ok(map),
+                    Err(err) => Err(format!("could not load map \"{}\": {}", map_name, err)),
+                };
+                match map {
+                    Ok(map) => {
+                        self.report.connect_duration = Some(Instant::now() - start_time);
+                        self.state = SimulatedClientState::Connected(
+                            ConnectedState::new(my_player_id, map, movement_params)
+                        );
+                    },
+                    Err(failure) => {
+                        socket.disconnect();
+                        self.state = SimulatedClientState::Disconnecting;
+                        return Err(failure);
+                    },
+                }
+            },
+            ClientSocketEvent::SnapshotReceived(snapshot) => {
+                let now = Instant::now();
+                let (first_tick, first_time) = *self.first_snapshot
+                    .get_or_insert((snapshot.tick(), now));
+                let expected = (snapshot.tick() as f64 - first_tick as f64)
+                    / TICK_SPEED.per_second() as f64;
+                let delay = util::duration_as_float(now - first_time) - expected;
+                self.report.snapshots += 1;
+                self.report.snapshot_delay_sum += delay;
+                self.report.snapshot_delay_square_sum += delay * delay;
+                if let SimulatedClientState::Connected(ref mut state) = self.state {
+                    state.on_snapshot(snapshot);
+                }
+            },
+            ClientSocketEvent::InputAckReceived { input_tick, arrival_tick_instant } => {
+                self.report.input_acks += 1;
+                let arrival = arrival_tick_instant.tick as f64 + arrival_tick_instant.intra_tick;
+                if arrival_tick_instant.tick >= input_tick {
+                    self.report.late_inputs += 1;
+                }
+                self.report.input_lead_sum += input_tick as f64 - arrival;
+                if let SimulatedClientState::Connected(ref mut state) = self.state {
+                    state.on_input_ack(input_tick, arrival_tick_instant);
+                }
+            },
+            ClientSocketEvent::StatsReceived(stats) => {
+                let min_tick_rate = self.report.min_server_tick_rate
+                    .map_or(stats.ticks_per_second, |rate| rate.min(stats.ticks_per_second));
+                self.report.min_server_tick_rate = Some(min_tick_rate);
+                self.report.max_server_clients = self.report.max_server_clients.max(stats.clients);
+                self.report.last_server_stats = Some(stats);
+            },
+            ClientSocketEvent::ChatReceived(_) | ClientSocketEvent::PlayerEventReceived(_) => (),
+            ClientSocketEvent::DoneDisconnecting
+            | ClientSocketEvent::DisconnectingConnectionEnd { .. } => {
+                self.state = SimulatedClientState::Disconnected;
+            },
+            ClientSocketEvent::ConnectionClosed => {
+                self.state = SimulatedClientState::Disconnected;
+                return Err(String::from("kicked by the server"));
+            },
+            ClientSocketEvent::ConnectionEnd { reason } => {
+                self.state = SimulatedClientState::Disconnected;
+                return Err(String::from(match reason {
+                    ConnectionEndReason::TimedOut => "timed out",
+                    ConnectionEndReason::Reset => "connection reset",
+                    ConnectionEndReason::Banned => "banned by the server",
+                }));
+            },
+            ClientSocketEvent::NetworkError(err) => {
+                self.state = SimulatedClientState::Disconnected;
+                return Err(format!("network error: {}", err));
+            },
+        }
+        Ok(())
+    }
+
+    fn update_input(&mut self) {
+        let tick = self.report.ticks;
+        let input = &mut self.input;
+        match self.config.input_mode {
+            InputMode::Idle => (),
+            InputMode::Scripted => {
+                input.forward = true;
+                input.view_dir.add_yaw(FPAngle::fraction(1, 4 * TICK_SPEED.per_second() as i64));
+                input.crouch = tick % TICK_SPEED.ticks(5.0) < TICK_SPEED.ticks(1.0);
+                if tick % TICK_SPEED.ticks(1.0) == 0 {
+                    input.num_jumps += 1;
+                }
+                input.fire = tick % TICK_SPEED.ticks(0.5) < TICK_SPEED.ticks(0.1);
+            },
+            InputMode::Random => {
+                let rng = &mut self.rng;
+                if tick % TICK_SPEED.ticks(0.5) == 0 {
+                    input.forward = rng.gen();
+                    input.backward = !input.forward && rng.gen();
+                    input.left = rng.gen();
+                    input.right = !input.left && rng.gen();
+                    input.crouch = rng.gen_weighted_bool(5);
+                    input.aim = rng.gen_weighted_bool(4);
+                    input.weapon = *rng.choose(&WEAPONS).unwrap();
+                }
+                input.view_dir.add_yaw(FPAngle::from_tau_float(rng.gen_range(-0.01, 0.01)));
+                input.view_dir.add_pitch(FPAngle::from_tau_float(rng.gen_range(-0.005, 0.005)));
+                if rng.gen_weighted_bool(TICK_SPEED.ticks(2.0) as u32) {
+                    input.num_jumps += 1;
+                }
+                input.fire = rng.gen_weighted_bool(5);
+            },
+        }
+    }
+}
\ No newline at end of file
@@ -18,12 +18,15 @@ use shared::util;
 use shared::consts;
 use shared::tick_time::TickInstant;
 use shared::net::Snapshot;
+use shared::net::ServerStats;
 use shared::net::socket::ConnectionEndReason;
 use shared::net::MAX_MESSAGE_LENGTH;
 use shared::net::socket::ConId;
 use shared::net::socket::WrappedUdpSocket;
 use shared::net::socket::ReliableSocket;
 use shared::net::socket::Event;
+use shared::net::stream_socket::StreamSocket;
+use shared::net::Transport;
 use shared::net::socket::CheckedMessage;
 use shared::net::socket::ConMessage;
 use shared::net::ClientMessage;
@@ -31,10 +34,13 @@ use shared::net::ConlessClientMessage::*;
 use shared::net::UnreliableClientMessage::*;
 use shared::net::ReliableClientMessage::*;
 use shared::net::ServerMessage;
+use shared::net::PlayerEvent;
 use shared::net::ConlessServerMessage::*;
 use shared::net::UnreliableServerMessage::*;
 use shared::net::ReliableServerMessage::*;
 use shared::model::world::character::CharacterInput;
+use shared::chat::ChatMessage;
+use shared::model::movement_params::MovementParams;
 
 use self::ClientSocketEvent::*;
 use self::InternalState::*;
@@ -44,12 +50,18 @@ use self::InternalState::*;
 pub enum ClientSocketEvent {
     DoneConnecting {
         my_player_id: u64,
+        map_name: String,
+        map_hash: u64,
+        movement_params: MovementParams,
     },
     SnapshotReceived(Snapshot),
+    ChatReceived(ChatMessage),
+    PlayerEventReceived(PlayerEvent),
     InputAckReceived {
         input_tick: u64,
         arrival_tick_instant: TickInstant,
     },
+    StatsReceived(ServerStats),
     DoneDisconnecting,
     DisconnectingConnectionEnd {
         reason: ConnectionEndReason,
@@ -76,22 +88,35 @@ enum InternalState {
 }
 
 pub struct ClientSocket {
-    socket: ReliableSocket<(), ClientMessage, ServerMessage, ConnectedSocket>,
+    socket: ReliableSocket<(), ClientMessage, ServerMessage, WrappedClientSocket>,
     //socket: ReliableSocket<(), ClientMessage, ServerMessage, CrapNetSocket>,
     internal_state: InternalState,
+    // sent with every connection request
+    player_name: String,
+    spectator: bool,
 }
 
 impl ClientSocket {
-    pub fn new(addr: SocketAddr) -> io::Result<ClientSocket> {
+    pub fn new(addr: SocketAddr, transport: Transport, player_name: String, spectator: bool)
+            -> io::Result<ClientSocket> {
+        let wrapped_socket = match transport {
+            Transport::Udp => WrappedClientSocket::Udp(ConnectedSocket::new(addr)?),
+            Transport::Tcp => WrappedClientSocket::Stream(StreamSocket::connect(addr)?),
+        };
         Ok(ClientSocket {
             socket: ReliableSocket::new(
-                ConnectedSocket::new(addr)?,
+                wrapped_socket,
                 //CrapNetSocket::new(addr, 0.5, 0.3, 0.3, 0.5, 0.3, 0.3)?,
                 consts::ack_timeout_duration(),
                 consts::disconnect_force_timeout(),
+                consts::idle_timeout_duration(),
+                consts::keepalive_interval(),
                 false,
+                None,
             ),
             internal_state: Connecting { resend_time: Instant::now() },
+            player_name,
+            spectator,
         })
     }
 
@@ -116,7 +141,10 @@ impl ClientSocket {
         match self.internal_state {
             Connecting { ref mut resend_time } => {
                 *resend_time = Instant::now() + consts::connection_request_resend_interval();
-                self.socket.send_to_conless((), ConnectionRequest);
+                self.socket.send_to_conless((), ConnectionRequest {
+                    name: self.player_name.clone(),
+                    spectator: self.spectator,
+                });
             },
             Connected { .. } | Disconnecting => {
                 self.socket.do_tick();
@@ -137,9 +165,33 @@ impl ClientSocket {
         }
     }
 
-    pub fn send_input(&mut self, tick: u64, input: CharacterInput) {
+    pub fn send_input(&mut self, tick: u64, input: CharacterInput, view_tick: u64) {
+        if let Connected { con_id } = self.internal_state {
+            self.socket.send_to_unreliable(con_id, InputMessage { tick, input, view_tick });
+        }
+    }
+
+    pub fn send_switch_team(&mut self, team: u64) {
         if let Connected { con_id } = self.internal_state {
-            self.socket.send_to_unreliable(con_id, InputMessage { tick, input });
+            self.socket.send_to_reliable(con_id, SwitchTeam { team });
+        }
+    }
+
+    pub fn send_chat(&mut self, text: String, team_only: bool) {
+        if let Connected { con_id } = self.internal_state {
+            self.socket.send_to_reliable(con_id, Chat { text, team_only });
+        }
+    }
+
+    pub fn send_set_spectator(&mut self, spectator: bool) {
+        if let Connected { con_id } = self.internal_state {
+            self.socket.send_to_reliable(con_id, SetSpectator { spectator });
+        }
+    }
+
+    pub fn send_rename(&mut self, name: String) {
+        if let Connected { con_id } = self.internal_state {
+            self.socket.send_to_reliable(con_id, Rename { name });
         }
     }
 
@@ -154,11 +206,21 @@ impl ClientSocket {
                     match msg {
                         CheckedMessage::Conless { clmsg, .. } => {
                             match clmsg {
-                                ConnectionAccept(player_id) => {
+                                ConnectionAccept {
+                                    player_id,
+                                    map_name,
+                                    map_hash,
+                                    movement_params,
+                                } => {
                                     if let Connecting { .. } = self.internal_state {
                                         let con_id = self.socket.connect(());
                                         self.internal_state = Connected { con_id };
-                                        return Some(DoneConnecting { my_player_id: player_id })
+                                        return Some(DoneConnecting {
+                                            my_player_id: player_id,
+                                            map_name,
+                                            map_hash,
+                                            movement_params,
+                                        })
                                     } else {
                                         println!(
                                             "DEBUG: Received connection accept while connected!"
@@ -176,15 +238,12 @@ impl ClientSocket {
                                             self.internal_state = Disconnected;
                                             return Some(ConnectionClosed);
                                         },
+                                        ChatBroadcast(msg) => return Some(ChatReceived(msg)),
+                                        PlayerEventBroadcast(event) => {
+                                            return Some(PlayerEventReceived(event))
+                                        },
                                     },
                                     ConMessage::Unreliable(umsg) => match umsg {
-                                        TimeOutMessage => {
-                                            self.socket.terminate(con_id);
-                                            self.internal_state = Disconnected;
-                                            return Some(ConnectionEnd {
-                                                reason: ConnectionEndReason::TimedOut
-                                            });
-                                        },
                                         SnapshotMessage(snapshot) => {
                                             return Some(SnapshotReceived(snapshot))
                                         },
@@ -194,6 +253,9 @@ impl ClientSocket {
                                                 arrival_tick_instant,
                                             });
                                         },
+                                        StatsMessage(stats) => {
+                                            return Some(StatsReceived(stats))
+                                        },
                                     }
                                 }
                             } else {
@@ -236,6 +298,41 @@ impl ClientSocket {
     }
 }
 
+enum WrappedClientSocket {
+    Udp(ConnectedSocket),
+    Stream(StreamSocket<()>),
+}
+
+impl WrappedUdpSocket<()> for WrappedClientSocket {
+    fn send_to(&mut self, buf: &[u8], addr: ()) -> io::Result<usize> {
+        match *self {
+            WrappedClientSocket::Udp(ref mut socket) => socket.send_to(buf, addr),
+            WrappedClientSocket::Stream(ref mut socket) => socket.send_to(buf, addr),
+        }
+    }
+
+    fn recv_from(&mut self, buf: &mut [u8]) -> io::Result<(usize, ())> {
+        match *self {
+            WrappedClientSocket::Udp(ref mut socket) => socket.recv_from(buf),
+            WrappedClientSocket::Stream(ref mut socket) => socket.recv_from(buf),
+        }
+    }
+
+    fn set_nonblocking(&mut self, nonblocking: bool) -> io::Result<()> {
+        match *self {
+            WrappedClientSocket::Udp(ref mut socket) => socket.set_nonblocking(nonblocking),
+            WrappedClientSocket::Stream(ref mut socket) => socket.set_nonblocking(nonblocking),
+        }
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        match *self {
+            WrappedClientSocket::Udp(ref mut socket) => socket.set_read_timeout(timeout),
+            WrappedClientSocket::Stream(ref mut socket) => socket.set_read_timeout(timeout),
+        }
+    }
+}
+
 struct ConnectedSocket {
     socket: UdpSocket,
 }
@@ -0,0 +1,352 @@
+use rand::Rng;
+
+use shared::consts::TICK_SPEED;
+use shared::map::Waypoint;
+use shared::math::FixedPoint;
+use shared::math::FPAngle;
+use shared::math::Vec3;
+use shared::model::Model;
+use shared::model::world::character::Character;
+use shared::model::world::character::CharacterInput;
+use shared::model::world::character::ViewDir;
+use shared::model::world::character::Weapon;
+use shared::model::world::entity::EntityId;
+use shared::model::world::level::Ray;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+// How well the bots of a difficulty play.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct BotSkill {
+    // in degrees per second
+    pub turn_speed: f64,
+    // in degrees, the aim is off by up to this much
+    pub aim_error: f64,
+    // in seconds, from seeing a target to firing at it
+    pub reaction_time: f64,
+    // in meters
+    pub view_distance: f64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BotConfig {
+    pub count: u64,
+    pub difficulty: Difficulty,
+    // tables have to come last in TOML
+    pub easy: BotSkill,
+    pub normal: BotSkill,
+    pub hard: BotSkill,
+}
+
+impl Default for BotConfig {
+    fn default() -> BotConfig {
+        BotConfig {
+            count: 0,
+            difficulty: Difficulty::Normal,
+            easy: BotSkill {
+                turn_speed: 180.0,
+                aim_error: 8.0,
+                reaction_time: 0.6,
+                view_distance: 25.0,
+            },
+            normal: BotSkill {
+                turn_speed: 360.0,
+                aim_error: 4.0,
+                reaction_time: 0.35,
+                view_distance: 40.0,
+            },
+            hard: BotSkill {
+                turn_speed: 720.0,
+                aim_error: 1.5,
+                reaction_time: 0.15,
+                view_distance: 60.0,
+            },
+        }
+    }
+}
+
+impl BotConfig {
+    pub fn skill(&self) -> BotSkill {
+        match self.difficulty {
+            Difficulty::Easy => self.easy,
+            Difficulty::Normal => self.normal,
+            Difficulty::Hard => self.hard,
+        }
+    }
+}
+
+// in degrees, bots fire when they aim at least this close to where they want to
+const FIRE_TOLERANCE: f64 = 5.0;
+// in meters
+const WAYPOINT_REACHED_DISTANCE: f64 = 1.0;
+const CHASE_DISTANCE: f64 = 5.0;
+// aim at the chest instead of the eyes
+const AIM_HEIGHT_OFFSET: f64 = 0.5;
+// in seconds
+const STUCK_TIME: f64 = 0.5;
+const AIM_ERROR_INTERVAL: f64 = 0.5;
+const STRAFE_INTERVAL: f64 = 1.0;
+
+// An AI-controlled player, it chases the nearest enemy in sight
+// and otherwise follows the map waypoints or wanders around.
+#[derive(Debug)]
+pub struct Bot {
+    player_id: u64,
+    // keeps the jumps counting and the view turning smoothly
+    input: CharacterInput,
+    target: Option<EntityId>,
+    // ticks the target has been in sight
+    sight_ticks: u64,
+    // in degrees, yaw and pitch
+    aim_error: (f64, f64),
+    strafe_right: bool,
+    waypoint: Option<usize>,
+    // in degrees
+    wander_yaw: f64,
+    // ticks until a new wander direction is chosen
+    wander_ticks: u64,
+    last_pos: Option<Vec3>,
+    stuck_ticks: u64,
+}
+
+impl Bot {
+    pub fn new(player_id: u64) -> Bot {
+        Bot {
+            player_id,
+            input: CharacterInput::default(),
+            target: None,
+            sight_ticks: 0,
+            aim_error: (0.0, 0.0),
+            strafe_right: false,
+            waypoint: None,
+            wander_yaw: 0.0,
+            wander_ticks: 0,
+            last_pos: None,
+            stuck_ticks: 0,
+        }
+    }
+
+    pub fn player_id(&self) -> u64 {
+        self.player_id
+    }
+
+    // None while the bot has no living character.
+    pub fn think<R: Rng>(&mut self, model: &Model, waypoints: &[Waypoint],
+                         skill: &BotSkill, rng: &mut R) -> Option<CharacterInput> {
+        let character = model.player(self.player_id)
+            .and_then(|player| player.character_id())
+            .and_then(|character_id| {
+                model.world().character(character_id).map(|character| (character_id, character))
+            });
+        let (character_id, character) = match character {
+            Some((character_id, character)) if character.alive() => (character_id, character),
+            _ => {
+                // start over after respawning
+                self.target = None;
+                self.waypoint = None;
+                self.last_pos = None;
+                return None;
+            },
+        };
+        let mut input = CharacterInput {
+            num_jumps: self.input.num_jumps,
+            weapon: self.input.weapon,
+            view_dir: self.input.view_dir,
+            ..CharacterInput::default()
+        };
+
+        let target = self.find_target(model, character_id, character, skill);
+        if target.map(|(target_id, _)| target_id) != self.target {
+            self.target = target.map(|(target_id, _)| target_id);
+            self.sight_ticks = 0;
+        }
+        // the input view direction is turned by teleporters
+        let mut wanted = match target {
+            Some((_, target_pos)) => {
+                if self.sight_ticks % TICK_SPEED.ticks(AIM_ERROR_INTERVAL).max(1) == 0 {
+                    let error = skill.aim_error;
+                    self.aim_error = (rng.gen_range(-error, error), rng.gen_range(-error, error));
+                }
+                if self.sight_ticks % TICK_SPEED.ticks(STRAFE_INTERVAL).max(1) == 0 {
+                    self.strafe_right = rng.gen();
+                }
+                self.sight_ticks += 1;
+                let mut aim_pos = target_pos;
+                aim_pos.z -= FixedPoint::from_float(AIM_HEIGHT_OFFSET);
+                let (yaw, pitch) = direction_angles(aim_pos - character.pos());
+                let distance = length(target_pos - character.pos());
+                input.forward = distance > CHASE_DISTANCE;
+                input.right = self.strafe_right;
+                input.left = !self.strafe_right;
+                input.weapon = choose_weapon(character, distance);
+                view_dir(yaw + self.aim_error.0, pitch + self.aim_error.1)
+            },
+            None => {
+                input.forward = true;
+                view_dir(self.navigate(character.pos(), waypoints, rng), 0.0)
+            },
+        };
+        wanted.add_yaw(-character.yaw_offset());
+        input.view_dir = turn(input.view_dir, wanted, skill);
+        if self.target.is_some() {
+            let (yaw_delta, pitch_delta) = input.view_dir.delta(&wanted);
+            let tolerance = FPAngle::from_tau_float(FIRE_TOLERANCE / 360.0);
+            input.fire = self.sight_ticks > TICK_SPEED.ticks(skill.reaction_time)
+                && yaw_delta.max(-yaw_delta) < tolerance
+                && pitch_delta.max(-pitch_delta) < tolerance;
+        }
+
+        // jump over whatever is in the way
+        let pos = character.pos();
+        let moved = self.last_pos.map_or(true, |last_pos| {
+            let mut offset = pos - last_pos;
+            offset.z = FixedPoint::new(0);
+            length(offset) > 0.01
+        });
+        self.last_pos = Some(pos);
+        if input.forward && !moved {
+            self.stuck_ticks += 1;
+            if self.stuck_ticks > TICK_SPEED.ticks(STUCK_TIME) {
+                input.num_jumps += 1;
+                self.wander_ticks = 0;
+                self.stuck_ticks = 0;
+            }
+        } else {
+            self.stuck_ticks = 0;
+        }
+
+        self.input = input;
+        Some(input)
+    }
+
+    // The id and eye position of the nearest living enemy in sight.
+    fn find_target(&self, model: &Model, character_id: EntityId, character: &Character,
+                   skill: &BotSkill) -> Option<(EntityId, Vec3)> {
+        let team = model.player(self.player_id).and_then(|player| player.team());
+        let world = model.world();
+        let mut best: Option<(EntityId, Vec3, f64)> = None;
+        for player in model.players().values() {
+            if team.is_some() && player.team() == team {
+                continue;
+            }
+            let target_id = match player.character_id() {
+                Some(target_id) => target_id,
+                None => continue,
+            };
+            let target = match world.character(target_id) {
+                Some(target) if target.alive() && target_id != character_id => target,
+                _ => continue,
+            };
+            let offset = target.pos() - character.pos();
+            let distance = length(offset);
+            if distance > skill.view_distance
+                || best.map_or(false, |(_, _, best_distance)| best_distance <= distance) {
+                continue;
+            }
+            // characters can overlap while spawning
+            let visible = offset.is_zero() || {
+                let ray = Ray {
+                    origin: character.pos(),
+                    dir: offset / FixedPoint::from_float(distance),
+                };
+                world.level().raycast(&ray, FixedPoint::from_float(distance)).is_none()
+            };
+            if visible {
+                best = Some((target_id, target.pos(), distance));
+            }
+        }
+        best.map(|(target_id, pos, _)| (target_id, pos))
+    }
+
+    // The yaw in degrees to walk in, along the waypoints if the map has any.
+    fn navigate<R: Rng>(&mut self, pos: Vec3, waypoints: &[Waypoint], rng: &mut R) -> f64 {
+        if waypoints.is_empty() {
+            if self.wander_ticks == 0 {
+                self.wander_yaw = rng.gen_range(0.0, 360.0);
+                self.wander_ticks = TICK_SPEED.ticks(rng.gen_range(1.0, 4.0));
+            }
+            self.wander_ticks -= 1;
+            return self.wander_yaw;
+        }
+        let horizontal_offset = |waypoint: &Waypoint| {
+            let mut offset = waypoint.pos - pos;
+            offset.z = FixedPoint::new(0);
+            offset
+        };
+        let index = match self.waypoint {
+            Some(index) => index,
+            None => {
+                // start at the closest one
+                let (index, _) = waypoints.iter()
+                    .map(|waypoint| length(horizontal_offset(waypoint)))
+                    .enumerate()
+                    .fold((0, f64::INFINITY), |best, (index, distance)| {
+                        if distance < best.1 { (index, distance) } else { best }
+                    });
+                index
+            },
+        };
+        let mut index = index;
+        if length(horizontal_offset(&waypoints[index])) < WAYPOINT_REACHED_DISTANCE {
+            let links = &waypoints[index].links;
+            index = if links.is_empty() {
+                rng.gen_range(0, waypoints.len())
+            } else {
+                links[rng.gen_range(0, links.len())]
+            };
+        }
+        self.waypoint = Some(index);
+        direction_angles(horizontal_offset(&waypoints[index])).0
+    }
+}
+
+// Prefers the launchers at a safe distance.
+fn choose_weapon(character: &Character, distance: f64) -> Weapon {
+    let usable = |weapon| {
+        character.has_weapon(weapon) && character.ammo(weapon).map_or(true, |ammo| ammo > 0)
+    };
+    if distance > 8.0 && usable(Weapon::RocketLauncher) {
+        Weapon::RocketLauncher
+    } else if distance > 5.0 && distance < 15.0 && usable(Weapon::GrenadeLauncher) {
+        Weapon::GrenadeLauncher
+    } else {
+        Weapon::Rifle
+    }
+}
+
+// Turns towards the wanted view direction as fast as the skill allows.
+fn turn(current: ViewDir, wanted: ViewDir, skill: &BotSkill) -> ViewDir {
+    let max_turn = FPAngle::from_tau_float(
+        skill.turn_speed / 360.0 / TICK_SPEED.per_second() as f64
+    );
+    let (yaw, pitch) = current.delta(&wanted);
+    let mut view_dir = current;
+    view_dir.add_yaw(yaw.max(-max_turn).min(max_turn));
+    view_dir.add_pitch(pitch.max(-max_turn).min(max_turn));
+    view_dir
+}
+
+// in degrees
+fn view_dir(yaw: f64, pitch: f64) -> ViewDir {
+    let mut view_dir = ViewDir::default();
+    view_dir.add_yaw(FPAngle::from_tau_float(yaw / 360.0));
+    view_dir.add_pitch(FPAngle::from_tau_float(pitch / 360.0));
+    view_dir
+}
+
+// The yaw and pitch in degrees looking along the vector.
+fn direction_angles(v: Vec3) -> (f64, f64) {
+    let (x, y, z): (f64, f64, f64) = (v.x.into(), v.y.into(), v.z.into());
+    (y.atan2(x).to_degrees(), z.atan2((x * x + y * y).sqrt()).to_degrees())
+}
+
+fn length(v: Vec3) -> f64 {
+    let (x, y, z): (f64, f64, f64) = (v.x.into(), v.y.into(), v.z.into());
+    (x * x + y * y + z * z).sqrt()
+}
\ No newline at end of file
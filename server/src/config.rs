@@ -0,0 +1,59 @@
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+
+use toml;
+
+use shared::consts::SERVER_CONFIG_FILE;
+use shared::consts::DEFAULT_MAP;
+use shared::ConfigParseError;
+use shared::net::Transport;
+use shared::model::game_mode::MatchRules;
+use shared::model::movement_params::MovementConfig;
+
+use input_validation::InputValidationConfig;
+use bot::BotConfig;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub transport: Transport,
+    pub map: String,
+    // tables have to come last in TOML
+    pub match_rules: MatchRules,
+    // maps can override these
+    pub movement: MovementConfig,
+    pub input_validation: InputValidationConfig,
+    pub bots: BotConfig,
+}
+
+impl Config {
+    pub fn load() -> Result<Config, ConfigParseError> {
+        let mut config_file = File::open(SERVER_CONFIG_FILE)?;
+
+        let mut config_string = String::new();
+        config_file.read_to_string(&mut config_string)?;
+        Ok(toml::from_str(&config_string)?)
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let config_string = toml::to_string(self)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        let mut file = File::create(SERVER_CONFIG_FILE)?;
+        file.write_all(config_string.as_bytes())
+    }
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            transport: Transport::Udp,
+            map: String::from(DEFAULT_MAP),
+            match_rules: MatchRules::default(),
+            movement: MovementConfig::default(),
+            input_validation: InputValidationConfig::default(),
+            bots: BotConfig::default(),
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,167 @@
+use std::time::Instant;
+
+use shared::consts::TICK_SPEED;
+use shared::math::FPAngle;
+use shared::model::world::character::CharacterInput;
+use shared::net::rate_limit::RateLimit;
+use shared::net::rate_limit::TokenBucket;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ViolationResponse {
+    // fix the input where possible, drop it otherwise
+    Clamp,
+    Drop,
+    Kick,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputValidationConfig {
+    pub response: ViolationResponse,
+    // more jumps than this within one tick have no effect anyway
+    pub max_jumps_per_tick: u64,
+    // averaged, clients send one input per tick but sometimes repeat one
+    pub max_inputs_per_tick: f64,
+    // inputs that may arrive at once, e.g. after a lag spike
+    pub input_burst: f64,
+    // in degrees, unlimited if not set
+    pub max_turn_per_tick: Option<f64>,
+}
+
+impl Default for InputValidationConfig {
+    fn default() -> InputValidationConfig {
+        InputValidationConfig {
+            response: ViolationResponse::Clamp,
+            max_jumps_per_tick: 1,
+            max_inputs_per_tick: 2.0,
+            input_burst: 60.0,
+            max_turn_per_tick: None,
+        }
+    }
+}
+
+impl InputValidationConfig {
+    fn input_rate_limit(&self) -> RateLimit {
+        RateLimit {
+            per_second: self.max_inputs_per_tick * TICK_SPEED.per_second() as f64,
+            burst: self.input_burst,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Violation {
+    // num_jumps decreased or increased too fast
+    Jumps,
+    InputRate,
+    // angles out of range or turning too fast
+    ViewDir,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Valid,
+    // the input was fixed and can be used
+    Clamped(Violation),
+    Dropped(Violation),
+    Kick(Violation),
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ViolationStats {
+    pub jumps: u64,
+    pub input_rate: u64,
+    pub view_dir: u64,
+    pub kicks: u64,
+}
+
+impl ViolationStats {
+    pub fn add(&mut self, verdict: Verdict) {
+        let violation = match verdict {
+            Verdict::Valid => return,
+            Verdict::Clamped(violation) | Verdict::Dropped(violation) => violation,
+            Verdict::Kick(violation) => {
+                self.kicks += 1;
+                violation
+            },
+        };
+        match violation {
+            Violation::Jumps => self.jumps += 1,
+            Violation::InputRate => self.input_rate += 1,
+            Violation::ViewDir => self.view_dir += 1,
+        }
+    }
+}
+
+// Checks the inputs of one client.
+#[derive(Debug)]
+pub struct InputValidator {
+    input_bucket: TokenBucket,
+    // the last input that was used and its tick
+    last_input: Option<(u64, CharacterInput)>,
+}
+
+impl InputValidator {
+    pub fn new(config: &InputValidationConfig, now: Instant) -> InputValidator {
+        InputValidator {
+            input_bucket: TokenBucket::new(&config.input_rate_limit(), now),
+            last_input: None,
+        }
+    }
+
+    // Called when an input arrives, excess inputs can't be clamped.
+    pub fn check_rate(&mut self, config: &InputValidationConfig, now: Instant) -> Verdict {
+        if self.input_bucket.take(&config.input_rate_limit(), now) {
+            Verdict::Valid
+        } else {
+            match reject(config, Violation::InputRate) {
+                Verdict::Clamped(violation) => Verdict::Dropped(violation),
+                verdict => verdict,
+            }
+        }
+    }
+
+    // Called in tick order right before the input gets used, it may be clamped in place.
+    pub fn validate(&mut self, config: &InputValidationConfig, tick: u64,
+                    input: &mut CharacterInput) -> Verdict {
+        let mut verdict = Verdict::Valid;
+        if !input.view_dir.is_normalized() {
+            verdict = reject(config, Violation::ViewDir);
+            input.view_dir = input.view_dir.normalized();
+        }
+        if let Some((last_tick, last_input)) = self.last_input {
+            // lost inputs leave gaps
+            let ticks = tick.saturating_sub(last_tick).max(1);
+            let max_jumps = last_input.num_jumps + config.max_jumps_per_tick * ticks;
+            if input.num_jumps < last_input.num_jumps || input.num_jumps > max_jumps {
+                verdict = reject(config, Violation::Jumps);
+                input.num_jumps = input.num_jumps.max(last_input.num_jumps).min(max_jumps);
+            }
+            if let Some(max_turn) = config.max_turn_per_tick {
+                let max_turn = FPAngle::from_tau_float(max_turn / 360.0 * ticks as f64);
+                let (yaw, pitch) = last_input.view_dir.delta(&input.view_dir);
+                let clamped_yaw = yaw.max(-max_turn).min(max_turn);
+                let clamped_pitch = pitch.max(-max_turn).min(max_turn);
+                if clamped_yaw != yaw || clamped_pitch != pitch {
+                    verdict = reject(config, Violation::ViewDir);
+                    input.view_dir = last_input.view_dir;
+                    input.view_dir.add_yaw(clamped_yaw);
+                    input.view_dir.add_pitch(clamped_pitch);
+                }
+            }
+        }
+        match verdict {
+            Verdict::Valid | Verdict::Clamped(_) => self.last_input = Some((tick, *input)),
+            Verdict::Dropped(_) | Verdict::Kick(_) => (),
+        }
+        verdict
+    }
+}
+
+fn reject(config: &InputValidationConfig, violation: Violation) -> Verdict {
+    match config.response {
+        ViolationResponse::Clamp => Verdict::Clamped(violation),
+        ViolationResponse::Drop => Verdict::Dropped(violation),
+        ViolationResponse::Kick => Verdict::Kick(violation),
+    }
+}
\ No newline at end of file
@@ -1,21 +1,46 @@
 mod socket;
+mod network;
+pub mod config;
+pub mod input_validation;
+pub mod bot;
 
 extern crate net2;
+extern crate mio;
+extern crate toml;
+extern crate rand;
+#[macro_use] extern crate serde_derive;
 
 extern crate shared;
 
-use std::thread;
 use std::time::Instant;
 use std::collections::HashMap;
+use std::collections::VecDeque;
 use std::io;
+use std::io::ErrorKind;
 use std::net::SocketAddr;
+use std::sync::mpsc;
+use std::sync::mpsc::Sender;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::RecvTimeoutError;
 
-use net2::UdpBuilder;
+use rand::XorShiftRng;
 
-use shared::consts;
 use shared::consts::TICK_SPEED;
 use shared::consts::MAX_INPUT_TICK_LEAD;
+use shared::consts::MAX_EVENTS_PER_TRAFFIC_LOOP;
+use shared::consts::MAX_LAG_COMPENSATION_TICKS;
+use shared::consts::ENTITY_CHECKSUM_INTERVAL;
+use shared::consts::CHAT_RATE_LIMIT;
+use shared::consts::RENAME_RATE_LIMIT;
+use shared::consts::SPECTATE_RATE_LIMIT;
+use shared::consts::DEFAULT_PLAYER_NAME;
 use shared::model::Model;
+use shared::model::player;
+use shared::model::movement_params::MovementParams;
+use shared::model::movement_params::MovementSettings;
+use shared::model::world::World;
+use shared::model::world::entity::EntityId;
+use shared::map::Map;
 use shared::model::world::character::CharacterInput;
 use shared::tick_time::TickInstant;
 use shared::net::socket::ConnectionEndReason;
@@ -23,89 +48,145 @@ use shared::net::socket::Event;
 use shared::net::socket::ConId;
 use shared::net::socket::CheckedMessage;
 use shared::net::socket::ConMessage;
-use shared::net::socket::ReliableSocket;
 use shared::net::ClientMessage;
 use shared::net::ConlessClientMessage::*;
 use shared::net::ReliableClientMessage::*;
 use shared::net::UnreliableClientMessage::*;
-use shared::net::ServerMessage;
 use shared::net::ConlessServerMessage::*;
 use shared::net::UnreliableServerMessage::*;
+use shared::net::ReliableServerMessage::*;
 use shared::net::Snapshot;
+use shared::net::ServerStats;
+use shared::net::TickChecksums;
+use shared::net::PlayerEvent;
+use shared::net::rate_limit::TokenBucket;
+use shared::chat;
+use shared::chat::ChatMessage;
 
-use socket::WrappedServerUdpSocket;
-use TickTarget::*;
+use network::NetworkHandle;
+use network::NetworkCommand;
+use network::ServerEvent;
+use config::Config;
+use input_validation::InputValidationConfig;
+use input_validation::InputValidator;
+use input_validation::Verdict;
+use input_validation::Violation;
+use input_validation::ViolationStats;
+use bot::Bot;
+use bot::BotSkill;
 
-enum TickTarget {
-    GameTick,
-    SocketTick,
+// Can be used from other threads to control a running server.
+#[derive(Clone)]
+pub struct ServerHandle {
+    events: Sender<ServerEvent>,
+}
+
+impl ServerHandle {
+    pub fn close(&self) {
+        self.events.send(ServerEvent::Close).ok();
+    }
 }
 
 #[derive(Debug)]
 struct Client {
     player_id: u64,
-    inputs: HashMap<u64, CharacterInput>,
-    last_input_time: Instant,
+    // the view tick of each input
+    inputs: HashMap<u64, (CharacterInput, u64)>,
+    // the tick the client was seeing when sending the input of the current tick
+    view_tick: u64,
+    chat_bucket: TokenBucket,
+    rename_bucket: TokenBucket,
+    spectate_bucket: TokenBucket,
+    input_validator: InputValidator,
 }
 
+// Runs the simulation, while a separate network thread does the socket I/O.
 pub struct Server {
-    socket: ReliableSocket<SocketAddr, ServerMessage, ClientMessage, WrappedServerUdpSocket>,
+    network: NetworkHandle,
+    event_sender: Sender<ServerEvent>,
+    event_receiver: Receiver<ServerEvent>,
     clients: HashMap<ConId, Client>, // TODO consider making this an array
     client_remove_buffer: Vec<ConId>, // TODO add remove reason for message
+    map: Map,
     model: Model,
+    // past worlds for lag compensation, oldest first
+    world_history: VecDeque<(u64, World)>,
     tick: u64,
+    start_tick_time: Instant,
     tick_time: Instant,
     next_tick_time: Instant,
     con_id_by_player_id: HashMap<u64, ConId>,
+    input_validation: InputValidationConfig,
+    violation_stats: ViolationStats,
+    bots: Vec<Bot>,
+    bot_skill: BotSkill,
+    bot_rng: XorShiftRng,
     closing: bool,
 }
 
 impl Server {
-    pub fn new() -> io::Result<Server> {
-        // create IPv6 UDP socket with IPv4 compatibility
-        let wrapped_socket = WrappedServerUdpSocket {
-            udp_socket: UdpBuilder::new_v6()?.only_v6(false)?.bind(("::", 51946))?,
-        };
+    pub fn new(config: &Config) -> io::Result<Server> {
+        let map = Map::load(&config.map).map_err(|err| io::Error::new(
+            ErrorKind::Other,
+            format!("Could not load map \"{}\": {}", config.map, err),
+        ))?;
+        println!("DEBUG: Loaded map \"{}\" ({:016x})!", map.name(), map.hash());
+        let movement_settings = config.movement.apply(&MovementSettings::default())
+            .and_then(|settings| map.movement().apply(&settings))
+            .map_err(|err| io::Error::new(ErrorKind::Other, err))?;
+        let movement_params = MovementParams::new(&movement_settings, TICK_SPEED);
+        let mut model = Model::new(map.level().clone(), config.match_rules);
+        model.set_movement_params(movement_params);
+        let mut bots = Vec::new();
+        for number in 1..(config.bots.count + 1) {
+            let player_id = model.add_player(format!("Bot {}", number), false);
+            bots.push(Bot::new(player_id));
+        }
+        if !bots.is_empty() {
+            println!("DEBUG: Added {} {:?} bots!", bots.len(), config.bots.difficulty);
+        }
+        let (event_sender, event_receiver) = mpsc::channel();
+        let network = network::spawn(config, event_sender.clone())?;
         Ok(Server {
-            socket: ReliableSocket::new(
-                wrapped_socket,
-                consts::ack_timeout_duration(),
-                consts::ack_timeout_duration(),
-                true,
-            ),
+            network,
+            event_sender,
+            event_receiver,
             clients: HashMap::new(),
             client_remove_buffer: Vec::new(),
-            model: Model::new(),
+            model,
+            map,
+            world_history: VecDeque::new(),
             tick: 0,
+            start_tick_time: Instant::now(),
             tick_time: Instant::now(),
             next_tick_time: Instant::now(),
             con_id_by_player_id: HashMap::new(),
+            input_validation: config.input_validation,
+            violation_stats: ViolationStats::default(),
+            bots,
+            bot_skill: config.bots.skill(),
+            bot_rng: rand::weak_rng(),
             closing: false,
         })
     }
 
+    pub fn handle(&self) -> ServerHandle {
+        ServerHandle {
+            events: self.event_sender.clone(),
+        }
+    }
+
     pub fn run(&mut self) {
         // for tick rate display
         let mut last_sec = Instant::now();
         let mut tick_counter = 0;
 
         // for sleep timing
-        let start_tick_time = Instant::now();
+        self.start_tick_time = Instant::now();
         self.next_tick_time = Instant::now();
 
         // main loop
-        while !self.closing { // TODO add way to exit
-            // check input timeouts
-            self.check_input_timeouts();
-
-            // socket tick
-            if let Some(next_socket_tick_time) = self.socket.next_tick_time() {
-                let before_tick = Instant::now();
-                if next_socket_tick_time <= before_tick {
-                    self.socket.do_tick();
-                }
-            }
-
+        while !self.closing {
             // game tick
             let before_tick = Instant::now();
             if self.next_tick_time <= before_tick {
@@ -114,122 +195,268 @@ impl Server {
 
                 // update tick times
                 self.tick_time = self.next_tick_time;
-                self.next_tick_time = start_tick_time + (self.tick + 1) / TICK_SPEED;
+                self.next_tick_time = self.start_tick_time + (self.tick + 1) / TICK_SPEED;
 
                 // tick
-                for (_, client) in self.clients.iter_mut() {
-                    if let Some(input) = client.inputs.remove(&self.tick) {
-                        self.model.set_character_input(client.player_id, input);
+                let phase = self.model.game_mode().phase();
+                let mut kicks = Vec::new();
+                for (&con_id, client) in self.clients.iter_mut() {
+                    if let Some((mut input, view_tick)) = client.inputs.remove(&self.tick) {
+                        let verdict = client.input_validator
+                            .validate(&self.input_validation, self.tick, &mut input);
+                        self.violation_stats.add(verdict);
+                        match verdict {
+                            Verdict::Valid | Verdict::Clamped(_) => {
+                                self.model.set_character_input(client.player_id, input);
+                                client.view_tick = view_tick;
+                            },
+                            Verdict::Dropped(_) => (),
+                            Verdict::Kick(violation) => kicks.push((con_id, violation)),
+                        }
+                    }
+                }
+                for (con_id, violation) in kicks {
+                    self.kick_client(con_id, violation);
+                }
+                for bot in self.bots.iter_mut() {
+                    let input = bot.think(
+                        &self.model,
+                        self.map.waypoints(),
+                        &self.bot_skill,
+                        &mut self.bot_rng,
+                    );
+                    if let Some(input) = input {
+                        self.model.set_character_input(bot.player_id(), input);
                     }
                 }
+                let before_tick_checksum = self.model.checksum();
                 self.model.do_tick();
-                let msg = SnapshotMessage(Snapshot::new(self.tick, &self.model));
-                self.socket.broadcast_unreliable(msg);
+                // the checksum leaves out the shots, clients can't rewind the world to check them
+                let checksums = TickChecksums {
+                    before_tick: before_tick_checksum,
+                    after_tick: self.model.checksum(),
+                    entities: if self.tick % ENTITY_CHECKSUM_INTERVAL == 0 {
+                        Some(self.model.world().entity_checksums())
+                    } else {
+                        None
+                    },
+                };
+                self.handle_shots();
+                if self.model.game_mode().phase() != phase {
+                    println!("DEBUG: Match phase changed to {:?}!", self.model.game_mode().phase());
+                }
+                self.world_history.push_back((self.tick, self.model.world().clone()));
+                while self.world_history.len() as u64 > MAX_LAG_COMPENSATION_TICKS {
+                    self.world_history.pop_front();
+                }
+                let msg = SnapshotMessage(Snapshot::new(self.tick, &self.model, checksums));
+                self.network.send(NetworkCommand::BroadcastUnreliable(msg));
+                self.network.flush();
                 tick_counter += 1;
 
                 // display tick rate
                 let now = Instant::now();
                 if now - last_sec > std::time::Duration::from_secs(1) {
-                    println!("ticks/s: {}, players: {}", tick_counter, self.clients.len());
+                    let flood_stats = self.network.flood_stats();
+                    let violation_stats = self.violation_stats;
+                    println!(
+                        "ticks/s: {}, players: {}, rate limited packets: {}, \
+                         banned packets: {}, bans: {}, input violations (jumps/rate/view): \
+                         {}/{}/{}, kicks: {}",
+                        tick_counter,
+                        self.clients.len(),
+                        flood_stats.rate_limited_packets,
+                        flood_stats.banned_packets,
+                        flood_stats.bans,
+                        violation_stats.jumps,
+                        violation_stats.input_rate,
+                        violation_stats.view_dir,
+                        violation_stats.kicks,
+                    );
+                    let stats = ServerStats {
+                        ticks_per_second: tick_counter,
+                        clients: self.clients.len() as u64,
+                        rate_limited_packets: flood_stats.rate_limited_packets,
+                        banned_packets: flood_stats.banned_packets,
+                        bans: flood_stats.bans,
+                        input_violations: violation_stats.jumps
+                            + violation_stats.input_rate
+                            + violation_stats.view_dir,
+                        kicks: violation_stats.kicks,
+                    };
+                    self.network.send(NetworkCommand::BroadcastUnreliable(StatsMessage(stats)));
                     tick_counter = 0;
                     last_sec += std::time::Duration::from_secs(1)
                 }
             }
 
-            // sleep / handle traffic
-            self.handle_traffic();
+            // sleep / handle events
+            self.handle_events();
         }
+        self.network.close();
     }
 
-    fn handle_traffic(&mut self) -> TickTarget {
-        loop {
-            let mut next_loop_time = self.next_tick_time;
-            let mut tick_target = GameTick;
-            match self.socket.next_tick_time() {
-                Some(next_socket_tick_time) if next_socket_tick_time < next_loop_time => {
-                    next_loop_time = next_socket_tick_time;
-                    tick_target = SocketTick;
-                }
-                _ => (),
+    // Checks the shots of this tick against the other characters as the shooter saw them.
+    fn handle_shots(&mut self) {
+        let view_ticks: HashMap<EntityId, u64> = self.clients.values()
+            .filter_map(|client| {
+                let player = self.model.player(client.player_id)?;
+                player.character_id().map(|character_id| (character_id, client.view_tick))
+            })
+            .collect();
+        let tick = self.tick;
+        let world_history = &self.world_history;
+        // rewind the targets, the shooter itself shoots from where it is now
+        let hits = self.model.world_mut().apply_shots(|character_id| {
+            // bots have no view tick, they see the current world
+            let view_tick = *view_ticks.get(&character_id)?;
+            let oldest_tick = tick.saturating_sub(MAX_LAG_COMPENSATION_TICKS);
+            let view_tick = view_tick.max(oldest_tick).min(tick);
+            world_history.iter()
+                .find(|&&(tick, _)| tick == view_tick)
+                .map(|&(_, ref past_world)| past_world)
+        });
+        for (character_id, hit) in hits {
+            println!(
+                "DEBUG: Character {} hit character {} at distance {}!",
+                character_id,
+                hit.character_id,
+                hit.distance,
+            );
+        }
+    }
+
+    fn handle_events(&mut self) {
+        // the budget makes sure the server continues ticking on DDoS
+        let mut handled_events = 0;
+        while handled_events < MAX_EVENTS_PER_TRAFFIC_LOOP {
+            let now = Instant::now();
+            if self.next_tick_time <= now {
+                break;
             }
-            match self.socket.wait_event(next_loop_time) {
-                Some(Event::MessageReceived(msg)) => self.handle_message(msg),
-                Some(Event::DoneDisconnecting(con_id)) => {
-                    println!("DEBUG: {} disconnected gracefully!", con_id);
-                }
-                Some(Event::ConnectionEnd { reason, con_id }) => {
-                    match reason {
-                        ConnectionEndReason::TimedOut => {
-                            println!("DEBUG: {} timed out!", con_id);
-                        },
-                        ConnectionEndReason::Reset => {
-                            println!("DEBUG: {} sent connection reset!", con_id);
-                        },
+            match self.event_receiver.recv_timeout(self.next_tick_time - now) {
+                Ok(ServerEvent::Network { event, recv_time }) => {
+                    handled_events += 1;
+                    self.handle_event(event, recv_time);
+                    if self.closing {
+                        break;
                     }
-                    self.remove_client(con_id)
                 },
-                Some(Event::DisconnectingConnectionEnd { reason, con_id }) => {
-                    match reason {
-                        ConnectionEndReason::TimedOut => {
-                            println!("DEBUG: {} timed out during disconnect!", con_id);
-                        },
-                        ConnectionEndReason::Reset => {
-                            println!("DEBUG: {} sent connection reset during disconnect!", con_id);
-                        },
-                    }
-                },
-                Some(Event::NetworkError(e)) => {
-                    println!("ERROR: Network broken: {:?}", e);
+                Ok(ServerEvent::Close) => {
                     self.closing = true;
-                    let now = Instant::now();
-                    if now < self.next_tick_time {
-                        thread::sleep(self.next_tick_time - now);
-                    }
-                    return tick_target; // this is not actually true, but we're closing anyway
+                    break;
+                },
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => unreachable!(), // we hold a sender ourselves
+            }
+        }
+        self.network.flush();
+    }
+
+    fn handle_event(&mut self, event: Event<SocketAddr, ClientMessage>, recv_time: Instant) {
+        match event {
+            Event::MessageReceived(msg) => self.handle_message(msg, recv_time),
+            Event::DoneDisconnecting(con_id) => {
+                println!("DEBUG: {} disconnected gracefully!", con_id);
+            }
+            Event::ConnectionEnd { reason, con_id } => {
+                match reason {
+                    ConnectionEndReason::TimedOut => {
+                        println!("DEBUG: {} timed out!", con_id);
+                    },
+                    ConnectionEndReason::Reset => {
+                        println!("DEBUG: {} sent connection reset!", con_id);
+                    },
+                    ConnectionEndReason::Banned => {
+                        println!("DEBUG: {} was banned!", con_id);
+                    },
                 }
-                None => return tick_target,
+                self.remove_client(con_id)
+            },
+            Event::DisconnectingConnectionEnd { reason, con_id } => {
+                match reason {
+                    ConnectionEndReason::TimedOut => {
+                        println!("DEBUG: {} timed out during disconnect!", con_id);
+                    },
+                    ConnectionEndReason::Reset => {
+                        println!("DEBUG: {} sent connection reset during disconnect!", con_id);
+                    },
+                    ConnectionEndReason::Banned => {
+                        println!("DEBUG: {} was banned during disconnect!", con_id);
+                    },
+                }
+            },
+            Event::NetworkError(e) => {
+                println!("ERROR: Network broken: {:?}", e);
+                self.closing = true;
             }
-            // TODO maybe add conditional break here, to make sure the server continues ticking on DDoS
         }
     }
 
-    fn handle_message(&mut self, msg: CheckedMessage<SocketAddr, ClientMessage>) {
+    fn handle_message(&mut self, msg: CheckedMessage<SocketAddr, ClientMessage>,
+                      recv_time: Instant) {
         if let CheckedMessage::Conful {
             con_id,
             cmsg: ConMessage::Reliable(DisconnectRequest)
         } = msg {
             self.remove_client(con_id);
-            self.socket.terminate(con_id);
+            self.network.send(NetworkCommand::Terminate(con_id));
             return;
         }
-        let recv_time = Instant::now();
         match msg {
             CheckedMessage::Conless { addr, con_id, clmsg } => {
                 match clmsg {
-                    ConnectionRequest => {
-                        let player_id = match con_id {
-                            Some(con_id) => {
+                    ConnectionRequest { name, spectator } => {
+                        // the network thread already connected new clients
+                        let con_id = match con_id {
+                            Some(con_id) => con_id,
+                            None => return,
+                        };
+                        let mut joined = false;
+                        let player_id = match self.clients.get(&con_id) {
+                            Some(client) => {
                                 // repeat confirm message
                                 // TODO what if the connection request is different from the first one?
-                                self.clients.get(&con_id).unwrap().player_id
+                                client.player_id
                             },
                             None => {
                                 // create new player
-                                let player_id = self.model.add_player(
-                                    String::from("UnknownPlayer")
-                                );
-                                let con_id = self.socket.connect(addr);
+                                let name = player::sanitize_name(&name)
+                                    .unwrap_or_else(|| String::from(DEFAULT_PLAYER_NAME));
+                                let player_id = self.model.add_player(name, spectator);
                                 self.con_id_by_player_id.insert(player_id, con_id);
                                 self.clients.insert(con_id, Client {
                                     player_id,
                                     inputs: HashMap::new(),
-                                    last_input_time: recv_time,
+                                    view_tick: self.tick,
+                                    chat_bucket: TokenBucket::new(&CHAT_RATE_LIMIT, recv_time),
+                                    rename_bucket: TokenBucket::new(
+                                        &RENAME_RATE_LIMIT,
+                                        recv_time,
+                                    ),
+                                    spectate_bucket: TokenBucket::new(
+                                        &SPECTATE_RATE_LIMIT,
+                                        recv_time,
+                                    ),
+                                    input_validator: InputValidator::new(
+                                        &self.input_validation,
+                                        recv_time,
+                                    ),
                                 });
-                                // TODO broadcast join message
+                                joined = true;
                                 player_id
                             },
                         };
-                        self.socket.send_to_conless(addr, ConnectionAccept(player_id));
+                        self.network.send(NetworkCommand::SendConless(addr, ConnectionAccept {
+                            player_id,
+                            map_name: String::from(self.map.name()),
+                            map_hash: self.map.hash(),
+                            movement_params: *self.model.world().movement_params(),
+                        }));
+                        if joined {
+                            let name = String::from(self.model.player(player_id).unwrap().name());
+                            self.broadcast_player_event(PlayerEvent::Joined { player_id, name });
+                        }
                     },
                     ConnectionAbort => {
                         if let Some(con_id) = con_id {
@@ -239,17 +466,119 @@ impl Server {
                 }
             },
             CheckedMessage::Conful { con_id, cmsg } => {
-                let client = self.clients.get_mut(&con_id).unwrap();
+                // messages can still be on their way when a client gets removed
+                let client = match self.clients.get_mut(&con_id) {
+                    Some(client) => client,
+                    None => return,
+                };
                 match cmsg {
                     ConMessage::Reliable(rmsg) => {
                         match rmsg {
                             DisconnectRequest => (), // handled earlier
+                            SwitchTeam { team } => {
+                                if self.model.switch_team(client.player_id, team) {
+                                    println!(
+                                        "DEBUG: Player {} switched to team {}!",
+                                        client.player_id,
+                                        team,
+                                    );
+                                } else {
+                                    println!(
+                                        "DEBUG: Player {} may not switch to team {}!",
+                                        client.player_id,
+                                        team,
+                                    );
+                                }
+                            },
+                            Chat { text, team_only } => {
+                                if !client.chat_bucket.take(&CHAT_RATE_LIMIT, recv_time) {
+                                    println!(
+                                        "DEBUG: Player {} is chatting too fast!",
+                                        client.player_id,
+                                    );
+                                    return;
+                                }
+                                let player_id = client.player_id;
+                                match chat::sanitize_text(&text) {
+                                    Some(text) => self.broadcast_chat(ChatMessage {
+                                        player_id,
+                                        team_only,
+                                        text,
+                                    }),
+                                    None => println!(
+                                        "DEBUG: Player {} sent an invalid chat message!",
+                                        player_id,
+                                    ),
+                                }
+                            },
+                            SetSpectator { spectator } => {
+                                let spectate_limit = &SPECTATE_RATE_LIMIT;
+                                if !client.spectate_bucket.take(spectate_limit, recv_time) {
+                                    println!(
+                                        "DEBUG: Player {} is toggling spectating too fast!",
+                                        client.player_id,
+                                    );
+                                    return;
+                                }
+                                if self.model.set_spectator(client.player_id, spectator) {
+                                    let player = self.model.player(client.player_id).unwrap();
+                                    let state = if player.playing() {
+                                        "playing"
+                                    } else if player.spectator() {
+                                        "spectating"
+                                    } else {
+                                        "waiting for a free slot"
+                                    };
+                                    println!("DEBUG: Player {} is {}!", client.player_id, state);
+                                }
+                            },
+                            Rename { name } => {
+                                if !client.rename_bucket.take(&RENAME_RATE_LIMIT, recv_time) {
+                                    println!(
+                                        "DEBUG: Player {} is renaming too fast!",
+                                        client.player_id,
+                                    );
+                                    return;
+                                }
+                                let player_id = client.player_id;
+                                let name = match player::sanitize_name(&name) {
+                                    Some(name) => name,
+                                    None => {
+                                        println!(
+                                            "DEBUG: Player {} requested an invalid name!",
+                                            player_id,
+                                        );
+                                        return;
+                                    },
+                                };
+                                let old_name = String::from(
+                                    self.model.player(player_id).unwrap().name()
+                                );
+                                let new_name = self.model.rename_player(player_id, name).unwrap();
+                                if new_name != old_name {
+                                    self.broadcast_player_event(PlayerEvent::Renamed {
+                                        player_id,
+                                        old_name,
+                                        new_name,
+                                    });
+                                }
+                            },
                         }
                     },
                     ConMessage::Unreliable(umsg) => {
                         match umsg {
-                            InputMessage { tick, input } => {
-                                client.last_input_time = recv_time;
+                            InputMessage { tick, input, view_tick } => {
+                                let verdict = client.input_validator
+                                    .check_rate(&self.input_validation, recv_time);
+                                self.violation_stats.add(verdict);
+                                match verdict {
+                                    Verdict::Valid | Verdict::Clamped(_) => (),
+                                    Verdict::Dropped(_) => return,
+                                    Verdict::Kick(violation) => {
+                                        self.kick_client(con_id, violation);
+                                        return;
+                                    },
+                                }
                                 if tick <= self.tick {
                                     println!(
                                         "Input came too late! | Current tick: {} | Target tick: {}",
@@ -264,20 +593,30 @@ impl Server {
                                         tick,
                                     );
                                 } else {
-                                    client.inputs.insert(tick, input);
+                                    client.inputs.insert(tick, (input, view_tick));
                                 }
-                                self.socket.send_to_unreliable(
+                                let arrival_tick_instant = if recv_time < self.tick_time {
+                                    // arrived before the last tick, while we were busy
+                                    TickInstant::from_start_tick(
+                                        self.start_tick_time,
+                                        recv_time,
+                                        TICK_SPEED,
+                                    )
+                                } else {
+                                    TickInstant::from_interval(
+                                        self.tick,
+                                        self.tick_time,
+                                        self.next_tick_time,
+                                        recv_time,
+                                    )
+                                };
+                                self.network.send(NetworkCommand::SendUnreliable(
                                     con_id,
                                     InputAck {
                                         input_tick: tick,
-                                        arrival_tick_instant: TickInstant::from_interval(
-                                            self.tick,
-                                            self.tick_time,
-                                            self.next_tick_time,
-                                            recv_time,
-                                        )
+                                        arrival_tick_instant,
                                     },
-                                );
+                                ));
                             },
                         }
                     },
@@ -286,16 +625,53 @@ impl Server {
         }
     }
 
-    fn check_input_timeouts(&mut self) {
-        let now = Instant::now();
-        for (&con_id, client) in self.clients.iter() {
-            if now > client.last_input_time + consts::input_timeout_duration() {
-                self.socket.send_to_unreliable(con_id, TimeOutMessage);
-                self.socket.terminate(con_id);
-                self.client_remove_buffer.push(con_id);
+    // Team messages only go to players in the same team as the sender.
+    fn broadcast_chat(&mut self, msg: ChatMessage) {
+        println!("DEBUG: Chat from player {}: {}", msg.player_id, msg.text);
+        let team = self.model.player(msg.player_id).and_then(|p| p.team());
+        for (con_id, client) in self.clients.iter() {
+            let recipient_team = self.model.player(client.player_id).and_then(|p| p.team());
+            if !msg.team_only || recipient_team == team {
+                self.network.send(NetworkCommand::SendReliable(
+                    *con_id,
+                    ChatBroadcast(msg.clone()),
+                ));
             }
         }
-        self.remove_clients();
+    }
+
+    fn broadcast_player_event(&mut self, event: PlayerEvent) {
+        match event {
+            PlayerEvent::Joined { player_id, ref name } => {
+                println!("DEBUG: Player {} joined as \"{}\"!", player_id, name);
+            },
+            PlayerEvent::Left { player_id, ref name } => {
+                println!("DEBUG: Player {} (\"{}\") left!", player_id, name);
+            },
+            PlayerEvent::Renamed { player_id, ref old_name, ref new_name } => {
+                println!(
+                    "DEBUG: Player {} renamed from \"{}\" to \"{}\"!",
+                    player_id,
+                    old_name,
+                    new_name,
+                );
+            },
+        }
+        self.network.send(NetworkCommand::BroadcastReliable(PlayerEventBroadcast(event)));
+    }
+
+    // The client gets told and removed right away, its remaining messages are ignored.
+    fn kick_client(&mut self, con_id: ConId, violation: Violation) {
+        if let Some(client) = self.clients.get(&con_id) {
+            println!(
+                "DEBUG: Kicking player {} for invalid input ({:?})!",
+                client.player_id,
+                violation,
+            );
+        }
+        self.network.send(NetworkCommand::SendReliable(con_id, ConnectionClose));
+        self.network.send(NetworkCommand::Disconnect(con_id));
+        self.remove_client(con_id);
     }
 
     fn remove_client(&mut self, con_id: ConId) {
@@ -304,11 +680,21 @@ impl Server {
     }
 
     fn remove_clients(&mut self) {
+        let mut events = Vec::new();
         for con_id in self.client_remove_buffer.drain(..) {
-            let client = self.clients.remove(&con_id).unwrap();
-            self.con_id_by_player_id.remove(&client.player_id).unwrap();
-            self.model.remove_player(client.player_id);
-            // TODO broadcast leave message
+            // the client may have been removed already by an earlier event
+            if let Some(client) = self.clients.remove(&con_id) {
+                self.con_id_by_player_id.remove(&client.player_id).unwrap();
+                if let Some(player) = self.model.remove_player(client.player_id) {
+                    events.push(PlayerEvent::Left {
+                        player_id: client.player_id,
+                        name: String::from(player.name()),
+                    });
+                }
+            }
+        }
+        for event in events {
+            self.broadcast_player_event(event);
         }
     }
 }
\ No newline at end of file
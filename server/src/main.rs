@@ -1,8 +1,20 @@
 extern crate server;
 
 use server::Server;
+use server::config::Config;
 
 fn main() {
-    let mut server = Server::new().unwrap();
+    let config = match Config::load() {
+        Ok(c) => c,
+        Err(err) => {
+            println!("Error while loading config: {}", err);
+            let c = Config::default();
+            if let Err(err) = c.save() {
+                println!("Error while saving config: {}", err);
+            }
+            c
+        }
+    };
+    let mut server = Server::new(&config).unwrap();
     server.run();
 }
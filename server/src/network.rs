@@ -0,0 +1,265 @@
+use std::io;
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Instant;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::mpsc;
+use std::sync::mpsc::Sender;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::TryRecvError;
+
+use net2::UdpBuilder;
+
+use shared::consts;
+use shared::consts::MAX_DATAGRAMS_PER_TRAFFIC_LOOP;
+use shared::consts::MAX_EVENTS_PER_TRAFFIC_LOOP;
+use shared::net::socket::Event;
+use shared::net::socket::ConId;
+use shared::net::socket::CheckedMessage;
+use shared::net::socket::ReliableSocket;
+use shared::net::event_poll::EventPoll;
+use shared::net::event_poll::WaitResult;
+use shared::net::event_poll::Waker;
+use shared::net::rate_limit::FloodStats;
+use shared::net::stream_socket::StreamSocket;
+use shared::net::Transport;
+use shared::net::ClientMessage;
+use shared::net::ConlessClientMessage::*;
+use shared::net::ServerMessage;
+use shared::net::ConlessServerMessage;
+use shared::net::ReliableServerMessage;
+use shared::net::UnreliableServerMessage;
+
+use socket::WrappedServerSocket;
+use config::Config;
+
+pub enum NetworkCommand {
+    SendConless(SocketAddr, ConlessServerMessage),
+    SendReliable(ConId, ReliableServerMessage),
+    SendUnreliable(ConId, UnreliableServerMessage),
+    BroadcastReliable(ReliableServerMessage),
+    BroadcastUnreliable(UnreliableServerMessage),
+    // sends the outstanding reliable messages first
+    Disconnect(ConId),
+    Terminate(ConId),
+    Close,
+}
+
+pub enum ServerEvent {
+    Network {
+        event: Event<SocketAddr, ClientMessage>,
+        recv_time: Instant,
+    },
+    Close,
+}
+
+// Used by the simulation thread to talk to the network thread.
+// Commands are only delivered after a flush, so a whole tick can be sent with one wakeup.
+pub struct NetworkHandle {
+    commands: Sender<NetworkCommand>,
+    waker: Waker,
+    flood_stats: Arc<Mutex<FloodStats>>,
+    thread: Option<JoinHandle<()>>,
+    unflushed: bool,
+}
+
+impl NetworkHandle {
+    pub fn send(&mut self, command: NetworkCommand) {
+        if self.commands.send(command).is_ok() {
+            self.unflushed = true;
+        }
+    }
+
+    pub fn flush(&mut self) {
+        if self.unflushed {
+            self.waker.wake();
+            self.unflushed = false;
+        }
+    }
+
+    pub fn flood_stats(&self) -> FloodStats {
+        *self.flood_stats.lock().unwrap()
+    }
+
+    pub fn close(&mut self) {
+        self.send(NetworkCommand::Close);
+        self.flush();
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                println!("ERROR: Network thread panicked!");
+            }
+        }
+    }
+}
+
+// Owns the socket and does all the receiving and sending,
+// so slow ticks don't delay the timestamps of incoming messages.
+struct Network {
+    socket: ReliableSocket<SocketAddr, ServerMessage, ClientMessage, WrappedServerSocket>,
+    event_poll: EventPoll,
+    commands: Receiver<NetworkCommand>,
+    events: Sender<ServerEvent>,
+    flood_stats: Arc<Mutex<FloodStats>>,
+    closing: bool,
+}
+
+pub fn spawn(config: &Config, events: Sender<ServerEvent>) -> io::Result<NetworkHandle> {
+    let event_poll = EventPoll::new()?;
+    let wrapped_socket = match config.transport {
+        Transport::Udp => {
+            // create IPv6 UDP socket with IPv4 compatibility
+            let udp_socket = UdpBuilder::new_v6()?.only_v6(false)?.bind(("::", 51946))?;
+            let udp_socket = mio::net::UdpSocket::from_socket(udp_socket)?;
+            event_poll.register_socket(&udp_socket)?;
+            WrappedServerSocket::Udp(udp_socket)
+        },
+        Transport::Tcp => {
+            // the stream socket wakes up the event poll when it receives something
+            let stream_socket = StreamSocket::listen(("::", 51946), Some(event_poll.waker()))?;
+            WrappedServerSocket::Stream(stream_socket)
+        },
+    };
+    let (command_sender, command_receiver) = mpsc::channel();
+    let flood_stats = Arc::new(Mutex::new(FloodStats::default()));
+    let waker = event_poll.waker();
+    let mut network = Network {
+        socket: ReliableSocket::new(
+            wrapped_socket,
+            consts::ack_timeout_duration(),
+            consts::ack_timeout_duration(),
+            consts::idle_timeout_duration(),
+            consts::keepalive_interval(),
+            true,
+            Some(consts::server_flood_protection()),
+        ),
+        event_poll,
+        commands: command_receiver,
+        events,
+        flood_stats: flood_stats.clone(),
+        closing: false,
+    };
+    let thread = thread::Builder::new()
+        .name(String::from("network"))
+        .spawn(move || network.run())?;
+    Ok(NetworkHandle {
+        commands: command_sender,
+        waker,
+        flood_stats,
+        thread: Some(thread),
+        unflushed: false,
+    })
+}
+
+impl Network {
+    fn run(&mut self) {
+        while !self.closing {
+            // socket tick
+            if let Some(next_socket_tick_time) = self.socket.next_tick_time() {
+                if next_socket_tick_time <= Instant::now() {
+                    self.socket.do_tick();
+                    *self.flood_stats.lock().unwrap() = self.socket.flood_stats();
+                }
+            }
+
+            // sleep / handle traffic
+            self.handle_traffic();
+        }
+    }
+
+    fn handle_traffic(&mut self) {
+        // the receive budget makes sure the socket ticks and commands get handled on DDoS,
+        // dropped datagrams count as well
+        let mut budget = MAX_DATAGRAMS_PER_TRAFFIC_LOOP;
+        let mut handled_events = 0;
+        while budget > 0 && handled_events < MAX_EVENTS_PER_TRAFFIC_LOOP {
+            if let Some(event) = self.socket.poll_event(&mut budget) {
+                let recv_time = Instant::now();
+                handled_events += 1;
+                self.forward_event(event, recv_time);
+                if self.closing {
+                    return;
+                }
+                continue;
+            }
+            if budget == 0 {
+                break;
+            }
+
+            // nothing to read, so wait for traffic, a command or the next socket tick
+            match self.event_poll.wait(self.socket.next_tick_time()) {
+                Ok(WaitResult::Readable) => (),
+                Ok(WaitResult::WokenUp) => {
+                    self.handle_commands();
+                    if self.closing {
+                        return;
+                    }
+                },
+                Ok(WaitResult::TimedOut) => return,
+                Err(e) => {
+                    println!("ERROR: Waiting for traffic failed: {:?}", e);
+                    self.closing = true;
+                    return;
+                },
+            }
+        }
+        println!("DEBUG: Receive budget exhausted!");
+        // commands may be waiting while we are flooded
+        self.handle_commands();
+    }
+
+    fn handle_commands(&mut self) {
+        loop {
+            match self.commands.try_recv() {
+                Ok(NetworkCommand::SendConless(addr, msg)) => {
+                    self.socket.send_to_conless(addr, msg);
+                },
+                Ok(NetworkCommand::SendReliable(con_id, msg)) => {
+                    self.socket.send_to_reliable(con_id, msg);
+                },
+                Ok(NetworkCommand::SendUnreliable(con_id, msg)) => {
+                    self.socket.send_to_unreliable(con_id, msg);
+                },
+                Ok(NetworkCommand::BroadcastReliable(msg)) => {
+                    self.socket.broadcast_reliable(msg);
+                },
+                Ok(NetworkCommand::BroadcastUnreliable(msg)) => {
+                    self.socket.broadcast_unreliable(msg);
+                },
+                Ok(NetworkCommand::Disconnect(con_id)) => self.socket.disconnect(con_id),
+                Ok(NetworkCommand::Terminate(con_id)) => self.socket.terminate(con_id),
+                Ok(NetworkCommand::Close) => self.closing = true,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    // the server was dropped without closing us
+                    self.closing = true;
+                    break;
+                },
+            }
+        }
+    }
+
+    fn forward_event(&mut self, event: Event<SocketAddr, ClientMessage>, recv_time: Instant) {
+        let event = match event {
+            Event::MessageReceived(CheckedMessage::Conless {
+                addr,
+                con_id: None,
+                clmsg: ConnectionRequest { name, spectator },
+            }) => {
+                // connect right away, so the simulation thread gets a connection to answer on
+                let con_id = self.socket.connect(addr);
+                Event::MessageReceived(CheckedMessage::Conless {
+                    addr,
+                    con_id: Some(con_id),
+                    clmsg: ConnectionRequest { name, spectator },
+                })
+            },
+            event => event,
+        };
+        if self.events.send(ServerEvent::Network { event, recv_time }).is_err() {
+            // the server is gone
+            self.closing = true;
+        }
+    }
+}
\ No newline at end of file
@@ -1,28 +1,43 @@
 use std::io;
+use std::io::ErrorKind;
 use std::time::Duration;
 use std::net::SocketAddr;
-use std::net::UdpSocket;
+
+use mio::net::UdpSocket;
 
 use shared::net::socket::WrappedUdpSocket;
+use shared::net::stream_socket::StreamSocket;
 
-pub struct WrappedServerUdpSocket {
-    pub udp_socket: UdpSocket,
+// Both variants are non-blocking, the server waits for them via an EventPoll.
+pub enum WrappedServerSocket {
+    Udp(UdpSocket),
+    Stream(StreamSocket<SocketAddr>),
 }
 
-impl WrappedUdpSocket<SocketAddr> for WrappedServerUdpSocket {
+impl WrappedUdpSocket<SocketAddr> for WrappedServerSocket {
     fn send_to(&mut self, buf: &[u8], addr: SocketAddr) -> io::Result<usize> {
-        self.udp_socket.send_to(buf, addr)
+        match *self {
+            WrappedServerSocket::Udp(ref mut socket) => socket.send_to(buf, &addr),
+            WrappedServerSocket::Stream(ref mut socket) => socket.send_to(buf, addr),
+        }
     }
 
     fn recv_from(&mut self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
-        self.udp_socket.recv_from(buf)
+        match *self {
+            WrappedServerSocket::Udp(ref mut socket) => socket.recv_from(buf),
+            WrappedServerSocket::Stream(ref mut socket) => socket.recv_from(buf),
+        }
     }
 
     fn set_nonblocking(&mut self, nonblocking: bool) -> io::Result<()> {
-        self.udp_socket.set_nonblocking(nonblocking)
+        if nonblocking {
+            Ok(())
+        } else {
+            Err(io::Error::new(ErrorKind::Other, "Server socket can't be blocking!"))
+        }
     }
 
-    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
-        self.udp_socket.set_read_timeout(timeout)
+    fn set_read_timeout(&mut self, _timeout: Option<Duration>) -> io::Result<()> {
+        Err(io::Error::new(ErrorKind::Other, "Server socket can't be blocking!"))
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,14 @@
+use consts::MAX_CHAT_MESSAGE_LENGTH;
+use util;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub player_id: u64,
+    // only sent to the team of the sender
+    pub team_only: bool,
+    pub text: String,
+}
+
+pub fn sanitize_text(text: &str) -> Option<String> {
+    util::sanitize_text(text, MAX_CHAT_MESSAGE_LENGTH)
+}
@@ -2,6 +2,8 @@ use std::time::Duration;
 use std::f64::consts::PI;
 
 use tick_time::TickRate;
+use net::rate_limit::RateLimit;
+use net::rate_limit::FloodProtection;
 
 // TODO make functions const
 
@@ -13,17 +15,104 @@ pub const TICK_SPEED: TickRate = TickRate { per_second: 120 };
 // physics
 // TODO move const fixed points from model here
 
+// characters
+pub const MAX_HEALTH: u64 = 100;
+pub const SPAWN_ARMOR: u64 = 50;
+// fraction of the damage taken by the armor, as long as there is armor left
+pub const ARMOR_ABSORPTION: (u64, u64) = (2, 3);
+// armor pickups don't go beyond this
+pub const MAX_ARMOR: u64 = 100;
+pub const RESPAWN_DELAY_TICKS: u64 = 3 * TICK_SPEED.per_second;
+
+// weapons
+// in meters
+pub const HITSCAN_RANGE: i64 = 200;
+pub const HITSCAN_DAMAGE: u64 = 20;
+// in seconds
+pub const RIFLE_FIRE_INTERVAL: f64 = 0.1;
+pub const ROCKET_LAUNCHER_FIRE_INTERVAL: f64 = 0.8;
+pub const GRENADE_LAUNCHER_FIRE_INTERVAL: f64 = 0.6;
+pub const MAX_ROCKETS: u64 = 25;
+pub const MAX_GRENADES: u64 = 30;
+// given by ammo and weapon pickups
+pub const ROCKET_PICKUP_AMMO: u64 = 5;
+pub const GRENADE_PICKUP_AMMO: u64 = 6;
+
+// projectiles
+// in meters per second
+pub const ROCKET_SPEED: f64 = 20.0;
+pub const GRENADE_SPEED: f64 = 15.0;
+// in meters per second squared
+pub const GRENADE_GRAVITY: f64 = 10.0;
+// the share of the velocity kept when bouncing
+pub const GRENADE_BOUNCINESS: f64 = 0.5;
+// in seconds
+pub const ROCKET_FUSE: f64 = 10.0;
+pub const GRENADE_FUSE: f64 = 2.5;
+// in meters
+pub const ROCKET_SPLASH_RADIUS: i64 = 3;
+pub const GRENADE_SPLASH_RADIUS: i64 = 4;
+pub const ROCKET_SPLASH_DAMAGE: u64 = 80;
+pub const GRENADE_SPLASH_DAMAGE: u64 = 100;
+
+// pickups
+pub const HEALTH_PICKUP_AMOUNT: u64 = 25;
+pub const ARMOR_PICKUP_AMOUNT: u64 = 50;
+// in seconds, until a taken pickup is back
+pub const HEALTH_PICKUP_RESPAWN_DELAY: f64 = 20.0;
+pub const ARMOR_PICKUP_RESPAWN_DELAY: f64 = 25.0;
+pub const AMMO_PICKUP_RESPAWN_DELAY: f64 = 15.0;
+pub const WEAPON_PICKUP_RESPAWN_DELAY: f64 = 30.0;
+
+// players
+// in characters
+pub const MAX_PLAYER_NAME_LENGTH: usize = 24;
+pub const DEFAULT_PLAYER_NAME: &'static str = "UnknownPlayer";
+
+// chat
+// in characters
+pub const MAX_CHAT_MESSAGE_LENGTH: usize = 200;
+
+// maps
+// relative to the client and server directories
+pub const MAP_DIRECTORY: &'static str = "../maps";
+pub const DEFAULT_MAP: &'static str = "plane";
+pub const MAX_MAP_NAME_LENGTH: usize = 64;
+
 // network
 pub fn ack_timeout_duration() -> Duration {
     Duration::from_secs(10)
 }
 pub const MAX_UNACKED_MESSAGES: usize = 1024;
+// Unreliable messages too long for one packet are split into at most this many.
+pub const MAX_MESSAGE_FRAGMENTS: usize = 16;
 pub fn initial_ack_duration_guess() -> Duration {
     Duration::new(0, 50000000)
 }
+// A stream peer that doesn't take our data for this long is dropped.
+pub fn stream_write_timeout() -> Duration {
+    Duration::from_millis(100)
+}
+// A stream peer with this many frames waiting to be written is dropped.
+pub const STREAM_OUTGOING_FRAMES: usize = 256;
+// Further stream connections are refused while this many are open.
+pub const MAX_STREAMS: usize = 64;
+// Reading from stream peers pauses while this many events wait to be handled.
+pub const STREAM_INCOMING_EVENTS: usize = 1024;
 pub const NEWEST_ACK_DURATION_WEIGHT: f64 = 0.001;
 pub const ACK_DURATION_SIGMA_FACTOR: f64 = 3.0;
 
+// If we didn't send anything for this long, we send an ack to keep the connection alive.
+pub fn keepalive_interval() -> Duration {
+    Duration::from_millis(500)
+}
+
+// If we didn't receive anything for this long, the connection is considered dead.
+// Several keepalive intervals, so a few lost keepalives don't end a connection.
+pub fn idle_timeout_duration() -> Duration {
+    Duration::from_secs(5)
+}
+
 // CLIENT
 
 pub const BASE_SPEED: TickRate = TickRate { per_second: 60 };
@@ -40,6 +129,21 @@ pub const DRAW_SPEED: TickRate = TickRate { per_second: 60 }; // TODO move to co
 pub const DEBUG_TEXT_FONT_SIZE: u32 = 20;
 pub const DEBUG_TEXT_HEIGHT: f64 = 0.025;
 pub const DEBUG_TEXT_RELATIVE_LINE_HEIGHT: f64 = 1.3;
+// in meters per second
+pub const SPECTATOR_CAMERA_SPEED: f64 = 10.0;
+pub const CHAT_LOG_LENGTH: usize = 100;
+pub const VISIBLE_CHAT_LINES: usize = 8;
+// chat lines are hidden after this time, unless the player is typing
+pub fn chat_line_display_duration() -> Duration {
+    Duration::from_secs(10)
+}
+// for characters without a team
+pub const DEFAULT_CHARACTER_COLOR: [f32; 3] = [1.0, 0.0, 0.0];
+pub const PROJECTILE_COLOR: [f32; 3] = [1.0, 0.8, 0.2];
+pub const HEALTH_PICKUP_COLOR: [f32; 3] = [0.2, 0.9, 0.3];
+pub const ARMOR_PICKUP_COLOR: [f32; 3] = [0.3, 0.7, 1.0];
+pub const AMMO_PICKUP_COLOR: [f32; 3] = [0.6, 0.6, 0.6];
+pub const WEAPON_PICKUP_COLOR: [f32; 3] = [0.9, 0.5, 0.1];
 
 // network
 //pub const MAX_PREDICT_TICKS: usize = 120;
@@ -80,8 +184,39 @@ pub fn disconnect_force_timeout() -> Duration {
 }
 
 // SERVER
+
+// files
+pub const SERVER_CONFIG_FILE: &'static str = "server_conf.toml";
+
 pub const MAX_INPUT_TICK_LEAD: u64 = 2000;
+// How far back in time shots are checked against what the shooter saw.
+// Clients that lag more than this have to lead their targets.
+pub const MAX_LAG_COMPENSATION_TICKS: u64 = 60;
+// Snapshots of every tick that is a multiple of this include the checksum of each entity.
+pub const ENTITY_CHECKSUM_INTERVAL: u64 = 30;
+// Maximum number of network events handled between two checks for a due tick.
+pub const MAX_EVENTS_PER_TRAFFIC_LOOP: usize = 256;
+// Maximum number of datagrams read between two checks, dropped ones included.
+pub const MAX_DATAGRAMS_PER_TRAFFIC_LOOP: usize = 1024;
+
+pub fn server_flood_protection() -> FloodProtection {
+    FloodProtection {
+        // connection requests are resent once per second
+        conless_limit: RateLimit { per_second: 5.0, burst: 20.0 },
+        // clients send inputs up to twice the tick speed plus acks
+        conful_limit: RateLimit { per_second: 500.0, burst: 1000.0 },
+        violation_limit: RateLimit { per_second: 50.0, burst: 1000.0 },
+        ban_duration: Duration::from_secs(60),
+    }
+}
+
+// chat messages per client, anything above is dropped
+pub const CHAT_RATE_LIMIT: RateLimit = RateLimit { per_second: 0.5, burst: 5.0 };
+// renames per client, every one is broadcast reliably
+pub const RENAME_RATE_LIMIT: RateLimit = RateLimit { per_second: 0.1, burst: 3.0 };
+// switches between spectating and playing per client
+pub const SPECTATE_RATE_LIMIT: RateLimit = RateLimit { per_second: 0.2, burst: 2.0 };
 
-pub fn input_timeout_duration() -> Duration {
+pub fn flood_protection_cleanup_interval() -> Duration {
     Duration::from_secs(10)
 }
\ No newline at end of file
@@ -2,6 +2,8 @@ pub mod consts;
 pub mod math;
 pub mod util;
 pub mod model;
+pub mod map;
+pub mod chat;
 pub mod net;
 pub mod tick_time;
 pub mod online_distribution;
@@ -15,6 +17,7 @@ extern crate bincode;
 #[macro_use] extern crate serde_derive;
 extern crate arrayvec;
 extern crate rand;
+extern crate mio;
 
 use std::fmt;
 use std::io;
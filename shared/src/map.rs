@@ -0,0 +1,409 @@
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::sync::Arc;
+
+use toml;
+
+use consts::MAP_DIRECTORY;
+use consts::MAX_MAP_NAME_LENGTH;
+use consts::TICK_SPEED;
+use math::FixedPoint;
+use math::FPAngle;
+use math::Vec3;
+use model::movement_params::MovementConfig;
+use model::movement_params::MovementSettings;
+use model::world::level::Level;
+use model::world::level::Shape;
+use model::world::level::Slope;
+use model::world::level::SpawnPoint;
+use model::world::character::Weapon;
+use model::world::pickup::PickupKind;
+use model::world::pickup::PickupSpawn;
+use model::world::trigger::Trigger;
+use model::world::trigger::TriggerAction;
+use util;
+
+pub const MAP_FORMAT_VERSION: u64 = 1;
+
+// keeps fixed-point products from overflowing
+const MAX_COORDINATE: f64 = 10000.0;
+
+#[derive(Debug)]
+pub struct MapLoadError(pub String);
+
+impl fmt::Display for MapLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<io::Error> for MapLoadError {
+    fn from(err: io::Error) -> Self {
+        MapLoadError(err.to_string())
+    }
+}
+
+impl From<toml::de::Error> for MapLoadError {
+    fn from(err: toml::de::Error) -> Self {
+        MapLoadError(err.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MapMeta {
+    pub title: String,
+    pub author: String,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Lighting {
+    pub ambient_light_color: [f32; 3],
+    pub directional_light_dir: [f32; 3],
+    pub directional_light_color: [f32; 3],
+    // the background is a checkerboard of two colors
+    pub background_color_1: [f32; 3],
+    pub background_color_2: [f32; 3],
+}
+
+impl Default for Lighting {
+    fn default() -> Lighting {
+        Lighting {
+            ambient_light_color: [0.30, 0.30, 0.35],
+            directional_light_dir: [1.00, 2.00, 3.00],
+            directional_light_color: [0.50, 0.50, 0.50],
+            background_color_1: [210.0 / 255.0, 163.0 / 255.0, 36.0 / 255.0],
+            background_color_2: [72.0 / 255.0, 67.0 / 255.0, 54.0 / 255.0],
+        }
+    }
+}
+
+// The map file uses floats and degrees, they are converted to fixed-point numbers on load.
+#[derive(Deserialize)]
+struct MapFile {
+    version: u64,
+    #[serde(default)]
+    meta: MapMeta,
+    #[serde(default)]
+    lighting: Lighting,
+    #[serde(default)]
+    geometry: GeometryFile,
+    #[serde(default)]
+    spawn_points: Vec<SpawnPointFile>,
+    #[serde(default)]
+    pickups: Vec<PickupFile>,
+    #[serde(default)]
+    triggers: Vec<TriggerFile>,
+    #[serde(default)]
+    waypoints: Vec<WaypointFile>,
+    // overrides the movement params of the server config
+    #[serde(default)]
+    movement: MovementConfig,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct GeometryFile {
+    floor: Option<f64>,
+    shapes: Vec<ShapeFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+enum ShapeFile {
+    Box {
+        min: [f64; 3],
+        max: [f64; 3],
+    },
+    OrientedBox {
+        center: [f64; 3],
+        half_extents: [f64; 3],
+        yaw: f64,
+    },
+    SlopedPlane {
+        min: [f64; 3],
+        max: [f64; 3],
+        slope: Slope,
+    },
+}
+
+#[derive(Deserialize)]
+struct SpawnPointFile {
+    pos: [f64; 3],
+    #[serde(default)]
+    yaw: f64,
+}
+
+#[derive(Deserialize)]
+enum PickupKindFile {
+    Health,
+    Armor,
+    Ammo,
+    Weapon,
+}
+
+#[derive(Deserialize)]
+struct PickupFile {
+    kind: PickupKindFile,
+    // only for ammo and weapons
+    weapon: Option<Weapon>,
+    pos: [f64; 3],
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+enum TriggerFile {
+    Teleport {
+        min: [f64; 3],
+        max: [f64; 3],
+        destination: [f64; 3],
+        yaw: Option<f64>,
+        #[serde(default)]
+        keep_velocity: bool,
+    },
+    // the velocity is in meters per second
+    JumpPad {
+        min: [f64; 3],
+        max: [f64; 3],
+        velocity: [f64; 3],
+    },
+    Kill {
+        min: [f64; 3],
+        max: [f64; 3],
+    },
+}
+
+#[derive(Deserialize)]
+struct WaypointFile {
+    pos: [f64; 3],
+    #[serde(default)]
+    links: Vec<usize>,
+}
+
+// Bots walk between waypoints, they are not part of the simulation.
+#[derive(Debug, Clone)]
+pub struct Waypoint {
+    pub pos: Vec3,
+    // indices of the waypoints that can be reached from here in a straight line
+    pub links: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Map {
+    name: String,
+    // identifies the exact file, so server and clients can check they use the same map
+    hash: u64,
+    meta: MapMeta,
+    lighting: Lighting,
+    level: Arc<Level>,
+    movement: MovementConfig,
+    waypoints: Vec<Waypoint>,
+}
+
+impl Map {
+    pub fn load(name: &str) -> Result<Map, MapLoadError> {
+        if !is_valid_map_name(name) {
+            return Err(MapLoadError(format!("Invalid map name \"{}\"!", name)));
+        }
+        let mut map_file = File::open(format!("{}/{}.toml", MAP_DIRECTORY, name))?;
+        let mut bytes = Vec::new();
+        map_file.read_to_end(&mut bytes)?;
+        Map::parse(name, &bytes)
+    }
+
+    pub fn parse(name: &str, bytes: &[u8]) -> Result<Map, MapLoadError> {
+        let map_file: MapFile = toml::from_slice(bytes)?;
+        if map_file.version != MAP_FORMAT_VERSION {
+            return Err(MapLoadError(format!(
+                "Unsupported map format version {}, expected {}!",
+                map_file.version,
+                MAP_FORMAT_VERSION,
+            )));
+        }
+        let floor = match map_file.geometry.floor {
+            Some(floor) => Some(convert_coordinate(floor)?),
+            None => None,
+        };
+        let mut shapes = Vec::with_capacity(map_file.geometry.shapes.len());
+        for shape in map_file.geometry.shapes {
+            shapes.push(convert_shape(shape)?);
+        }
+        // the final settings also depend on the server config, this only catches bad values early
+        map_file.movement.apply(&MovementSettings::default()).map_err(MapLoadError)?;
+        let mut spawn_points = Vec::with_capacity(map_file.spawn_points.len());
+        for spawn_point in map_file.spawn_points {
+            spawn_points.push(SpawnPoint {
+                pos: convert_vector(spawn_point.pos)?,
+                yaw: FPAngle::from_tau_float(spawn_point.yaw / 360.0),
+            });
+        }
+        let mut pickups = Vec::with_capacity(map_file.pickups.len());
+        for pickup in map_file.pickups {
+            pickups.push(PickupSpawn {
+                kind: convert_pickup_kind(pickup.kind, pickup.weapon)?,
+                pos: convert_vector(pickup.pos)?,
+            });
+        }
+        let mut triggers = Vec::with_capacity(map_file.triggers.len());
+        for trigger in map_file.triggers {
+            triggers.push(convert_trigger(trigger)?);
+        }
+        let num_waypoints = map_file.waypoints.len();
+        let mut waypoints = Vec::with_capacity(num_waypoints);
+        for (index, waypoint) in map_file.waypoints.into_iter().enumerate() {
+            if let Some(&link) = waypoint.links.iter()
+                .find(|&&link| link >= num_waypoints || link == index) {
+                return Err(MapLoadError(format!(
+                    "Waypoint {} has an invalid link to {}!",
+                    index,
+                    link,
+                )));
+            }
+            waypoints.push(Waypoint {
+                pos: convert_vector(waypoint.pos)?,
+                links: waypoint.links,
+            });
+        }
+        Ok(Map {
+            name: String::from(name),
+            hash: util::fnv1a(bytes),
+            meta: map_file.meta,
+            lighting: map_file.lighting,
+            level: Arc::new(Level::new(floor, shapes, spawn_points, pickups, triggers)),
+            movement: map_file.movement,
+            waypoints,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    pub fn meta(&self) -> &MapMeta {
+        &self.meta
+    }
+
+    pub fn lighting(&self) -> &Lighting {
+        &self.lighting
+    }
+
+    pub fn level(&self) -> &Arc<Level> {
+        &self.level
+    }
+
+    pub fn movement(&self) -> &MovementConfig {
+        &self.movement
+    }
+
+    pub fn waypoints(&self) -> &[Waypoint] {
+        &self.waypoints
+    }
+}
+
+// Map names come from the network and are used as file names.
+pub fn is_valid_map_name(name: &str) -> bool {
+    !name.is_empty() && name.len() <= MAX_MAP_NAME_LENGTH
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn convert_coordinate(value: f64) -> Result<FixedPoint, MapLoadError> {
+    if value.is_finite() && value.abs() <= MAX_COORDINATE {
+        Ok(FixedPoint::from_float(value))
+    } else {
+        Err(MapLoadError(format!("Coordinate {} is out of range!", value)))
+    }
+}
+
+fn convert_vector(value: [f64; 3]) -> Result<Vec3, MapLoadError> {
+    Ok(Vec3::new(
+        convert_coordinate(value[0])?,
+        convert_coordinate(value[1])?,
+        convert_coordinate(value[2])?,
+    ))
+}
+
+fn convert_bounds(min: [f64; 3], max: [f64; 3]) -> Result<(Vec3, Vec3), MapLoadError> {
+    let min = convert_vector(min)?;
+    let max = convert_vector(max)?;
+    if min.x < max.x && min.y < max.y && min.z < max.z {
+        Ok((min, max))
+    } else {
+        Err(MapLoadError(format!("Shape bounds {} to {} are empty!", min, max)))
+    }
+}
+
+fn convert_shape(shape: ShapeFile) -> Result<Shape, MapLoadError> {
+    Ok(match shape {
+        ShapeFile::Box { min, max } => {
+            let (min, max) = convert_bounds(min, max)?;
+            Shape::Box { min, max }
+        },
+        ShapeFile::OrientedBox { center, half_extents, yaw } => {
+            let (_, half_extents) = convert_bounds([0.0; 3], half_extents)?;
+            Shape::OrientedBox {
+                center: convert_vector(center)?,
+                half_extents,
+                yaw: FPAngle::from_tau_float(yaw / 360.0),
+            }
+        },
+        ShapeFile::SlopedPlane { min, max, slope } => {
+            let (min, max) = convert_bounds(min, max)?;
+            Shape::SlopedPlane { min, max, slope }
+        },
+    })
+}
+
+fn convert_pickup_kind(kind: PickupKindFile, weapon: Option<Weapon>)
+-> Result<PickupKind, MapLoadError> {
+    match (kind, weapon) {
+        (PickupKindFile::Health, None) => Ok(PickupKind::Health),
+        (PickupKindFile::Armor, None) => Ok(PickupKind::Armor),
+        (PickupKindFile::Ammo, Some(weapon)) if weapon.max_ammo().is_some() => {
+            Ok(PickupKind::Ammo(weapon))
+        },
+        (PickupKindFile::Ammo, Some(weapon)) => {
+            Err(MapLoadError(format!("Weapon {:?} doesn't use ammo!", weapon)))
+        },
+        (PickupKindFile::Weapon, Some(weapon)) => Ok(PickupKind::Weapon(weapon)),
+        (PickupKindFile::Ammo, None) | (PickupKindFile::Weapon, None) => {
+            Err(MapLoadError(String::from("Ammo and weapon pickups need a weapon!")))
+        },
+        (PickupKindFile::Health, Some(_)) | (PickupKindFile::Armor, Some(_)) => {
+            Err(MapLoadError(String::from("Only ammo and weapon pickups have a weapon!")))
+        },
+    }
+}
+
+fn convert_trigger(trigger: TriggerFile) -> Result<Trigger, MapLoadError> {
+    let (min, max, action) = match trigger {
+        TriggerFile::Teleport { min, max, destination, yaw, keep_velocity } => {
+            (min, max, TriggerAction::Teleport {
+                destination: convert_vector(destination)?,
+                yaw: yaw.map(|yaw| FPAngle::from_tau_float(yaw / 360.0)),
+                keep_velocity,
+            })
+        },
+        TriggerFile::JumpPad { min, max, velocity } => {
+            // only checks the range
+            convert_vector(velocity)?;
+            (min, max, TriggerAction::JumpPad {
+                velocity: Vec3::new(
+                    TICK_SPEED.per_tick(velocity[0]),
+                    TICK_SPEED.per_tick(velocity[1]),
+                    TICK_SPEED.per_tick(velocity[2]),
+                ),
+            })
+        },
+        TriggerFile::Kill { min, max } => (min, max, TriggerAction::Kill),
+    };
+    let (min, max) = convert_bounds(min, max)?;
+    Ok(Trigger { min, max, action })
+}
\ No newline at end of file
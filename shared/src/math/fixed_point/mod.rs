@@ -47,6 +47,16 @@ impl FixedPoint {
         FixedPoint((nominator << FP_PRECISION) / denominator)
     }
 
+    // Rounds to the nearest representable value.
+    pub fn from_float(float: f64) -> FixedPoint {
+        FixedPoint((float * FP_RESOLUTION as f64).round() as i64)
+    }
+
+    // Rounds towards negative infinity.
+    pub fn floor(self) -> i64 {
+        self.0 >> FP_PRECISION
+    }
+
     pub fn abs(&self) -> FixedPoint {
         FixedPoint(self.0.abs())
     }
@@ -63,6 +73,14 @@ impl FixedPoint {
         FixedPoint(approx)
     }
 
+    // Exact to the last bit, unlike inv_sqrt it is precise for small numbers.
+    pub fn sqrt(self) -> FixedPoint {
+        if self.0 < 0 {
+            panic!("Attempted to take square root of negative number!");
+        }
+        FixedPoint(int_sqrt((self.0 as u64) << FP_PRECISION) as i64)
+    }
+
     pub fn is_zero(&self) -> bool {
         self.0 == 0
     }
@@ -88,6 +106,26 @@ fn fp_div(a: i64, b: i64) -> i64 {
     (a << FP_PRECISION) / b
 }
 
+// rounds down
+fn int_sqrt(n: u64) -> u64 {
+    let mut remainder = n;
+    let mut root = 0;
+    let mut bit = 1 << 62;
+    while bit > n {
+        bit >>= 2;
+    }
+    while bit != 0 {
+        if remainder >= root + bit {
+            remainder -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+        bit >>= 2;
+    }
+    root
+}
+
 impl Mul<FixedPoint> for FixedPoint {
     type Output = FixedPoint;
 
@@ -6,6 +6,7 @@ use std::ops::AddAssign;
 use std::ops::SubAssign;
 use std::ops::MulAssign;
 use std::ops::DivAssign;
+use std::ops::Neg;
 use std::fmt;
 
 use cgmath::Vector3;
@@ -14,7 +15,7 @@ use math::fixed_point::FixedPoint;
 
 // TODO consider using cgmath's vector struct instead
 
-#[derive(Clone, Copy, Serialize, Deserialize)]
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Vec3 {
     pub x: FixedPoint,
     pub y: FixedPoint,
@@ -79,6 +80,18 @@ impl Sub for Vec3 {
     }
 }
 
+impl Neg for Vec3 {
+    type Output = Vec3;
+
+    fn neg(self) -> Vec3 {
+        Vec3 {
+            x: -self.x,
+            y: -self.y,
+            z: -self.z,
+        }
+    }
+}
+
 impl Mul<FixedPoint> for Vec3 {
     type Output = Vec3;
 
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+
+use consts::TICK_SPEED;
+use model::player::Player;
+use model::team::Team;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameModeKind {
+    FreeForAll,
+    TeamDeathmatch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Phase {
+    // for getting ready, scores are reset when it ends
+    Warmup,
+    // starts with fresh scores
+    Playing,
+    // the world stands still and shows the result
+    Intermission,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MatchRules {
+    pub kind: GameModeKind,
+    pub time_limit_secs: Option<u64>,
+    // for players in free-for-all, for teams in team deathmatch
+    pub score_limit: Option<i64>,
+    pub warmup_secs: u64,
+    pub intermission_secs: u64,
+    // further players have to spectate until a slot is free
+    pub max_players: Option<u64>,
+}
+
+impl Default for MatchRules {
+    fn default() -> MatchRules {
+        MatchRules {
+            kind: GameModeKind::FreeForAll,
+            time_limit_secs: Some(600),
+            score_limit: Some(20),
+            warmup_secs: 10,
+            intermission_secs: 10,
+            max_players: None,
+        }
+    }
+}
+
+// Drives the match through its phases.
+// It is part of the model, so clients know the phase and can show the remaining time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameMode {
+    rules: MatchRules,
+    phase: Phase,
+    // ticks since the phase started
+    phase_ticks: u64,
+}
+
+impl GameMode {
+    pub fn new(rules: MatchRules) -> GameMode {
+        GameMode {
+            rules,
+            phase: Phase::Warmup,
+            phase_ticks: 0,
+        }
+    }
+
+    pub fn rules(&self) -> &MatchRules {
+        &self.rules
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    // None if the phase has no time limit
+    pub fn remaining_ticks(&self) -> Option<u64> {
+        self.phase_duration().map(|duration| duration.saturating_sub(self.phase_ticks))
+    }
+
+    pub fn has_teams(&self) -> bool {
+        self.rules.kind == GameModeKind::TeamDeathmatch
+    }
+
+    pub fn has_free_slot(&self, players: &HashMap<u64, Player>) -> bool {
+        match self.rules.max_players {
+            Some(max_players) => {
+                (players.values().filter(|p| p.playing()).count() as u64) < max_players
+            },
+            None => true,
+        }
+    }
+
+    // Puts new players into the smallest team, ties go to the lower team id.
+    pub fn assign_team(&self, players: &HashMap<u64, Player>, teams: &[Team]) -> Option<u64> {
+        if !self.has_teams() {
+            return None;
+        }
+        let team_sizes = team_sizes(players, teams);
+        (0..teams.len() as u64).min_by_key(|&team| (team_sizes[team as usize], team))
+    }
+
+    // Players may only switch to a team that is smaller than their current one,
+    // so switching never makes the teams more uneven.
+    // Spectators have no team.
+    pub fn can_switch_team(&self, players: &HashMap<u64, Player>, teams: &[Team],
+                           from: Option<u64>, to: u64) -> bool {
+        if !self.has_teams() || to >= teams.len() as u64 || from == Some(to) {
+            return false;
+        }
+        let team_sizes = team_sizes(players, teams);
+        match from {
+            Some(from) => team_sizes[to as usize] < team_sizes[from as usize],
+            None => true,
+        }
+    }
+
+    // Returns the points for the killer, kills of teammates and oneself are punished.
+    pub fn kill_points(&self, killer: &Player, victim: &Player, suicide: bool) -> i64 {
+        if suicide || (self.has_teams() && killer.team() == victim.team()) {
+            -1
+        } else {
+            1
+        }
+    }
+
+    // Returns the new phase, if it changed.
+    pub fn do_tick(&mut self, players: &HashMap<u64, Player>, teams: &[Team]) -> Option<Phase> {
+        self.phase_ticks += 1;
+        let phase_over = match self.remaining_ticks() {
+            Some(remaining_ticks) => remaining_ticks == 0,
+            None => false,
+        };
+        let next_phase = match self.phase {
+            Phase::Warmup if phase_over => Some(Phase::Playing),
+            Phase::Playing if phase_over || self.score_limit_reached(players, teams) => {
+                Some(Phase::Intermission)
+            },
+            Phase::Intermission if phase_over => Some(Phase::Warmup),
+            _ => None,
+        };
+        if let Some(phase) = next_phase {
+            self.phase = phase;
+            self.phase_ticks = 0;
+        }
+        next_phase
+    }
+
+    fn phase_duration(&self) -> Option<u64> {
+        let secs = match self.phase {
+            Phase::Warmup => Some(self.rules.warmup_secs),
+            Phase::Playing => self.rules.time_limit_secs,
+            Phase::Intermission => Some(self.rules.intermission_secs),
+        };
+        secs.map(|secs| secs * TICK_SPEED.per_second())
+    }
+
+    fn score_limit_reached(&self, players: &HashMap<u64, Player>, teams: &[Team]) -> bool {
+        let score_limit = match self.rules.score_limit {
+            Some(score_limit) => score_limit,
+            None => return false,
+        };
+        if self.has_teams() {
+            teams.iter().any(|team| team.score() >= score_limit)
+        } else {
+            players.values().any(|player| player.score() >= score_limit)
+        }
+    }
+}
+
+fn team_sizes(players: &HashMap<u64, Player>, teams: &[Team]) -> Vec<u64> {
+    let mut team_sizes = vec![0; teams.len()];
+    for player in players.values() {
+        if let Some(size) = player.team().and_then(|team| team_sizes.get_mut(team as usize)) {
+            *size += 1;
+        }
+    }
+    team_sizes
+}
\ No newline at end of file
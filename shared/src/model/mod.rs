@@ -1,11 +1,25 @@
 pub mod world;
 pub mod player;
+pub mod game_mode;
+pub mod team;
+pub mod movement_params;
 
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use self::player::Player;
+use self::game_mode::GameMode;
+use self::game_mode::MatchRules;
+use self::game_mode::Phase;
+use self::team::Team;
+use self::movement_params::MovementParams;
 use self::world::World;
+use self::world::Kill;
+use self::world::entity::EntityId;
+use self::world::level::Level;
 use self::world::character::CharacterInput;
+use consts::MAX_PLAYER_NAME_LENGTH;
+use util::Fnv1a;
 
 // TODO maybe replace ids with weak references?
 
@@ -13,34 +27,89 @@ use self::world::character::CharacterInput;
 pub struct Model {
     players: HashMap<u64, Player>,
     world: World,
+    game_mode: GameMode,
+    // empty if the game mode has no teams
+    teams: Vec<Team>,
     next_player_id: u64,
 }
 
 impl Model {
-    pub fn new() -> Model {
+    pub fn new(level: Arc<Level>, rules: MatchRules) -> Model {
+        let game_mode = GameMode::new(rules);
+        let teams = if game_mode.has_teams() {
+            team::default_teams()
+        } else {
+            Vec::new()
+        };
         Model {
             players: HashMap::new(),
-            world: world::World::new(),
+            world: world::World::new(level),
+            game_mode,
+            teams,
             next_player_id: 0,
         }
     }
 
+    // Levels aren't part of snapshots, so they have to be set again after receiving one.
+    pub fn set_level(&mut self, level: Arc<Level>) {
+        self.world.set_level(level);
+    }
+
+    // Movement params aren't part of snapshots either.
+    pub fn set_movement_params(&mut self, movement_params: MovementParams) {
+        self.world.set_movement_params(movement_params);
+    }
+
     pub fn set_character_input(&mut self, player_id: u64, input: CharacterInput) {
         if let Some(character_id) = self.players.get(&player_id).unwrap().character_id() {
             self.world.set_character_input(character_id, input);
         }
     }
 
-    pub fn add_player(&mut self, name: String) -> u64 {
+    // Players who want to play may still have to spectate, if the game mode is full.
+    pub fn add_player(&mut self, name: String, spectator: bool) -> u64 {
+        let name = self.unique_name(name, None);
         let id = self.next_player_id;
-        let character_id = self.world.spawn_character();
         let mut player = Player::new(name);
-        player.set_character_id(Some(character_id));
+        player.set_spectator(spectator);
         self.players.insert(id, player);
         self.next_player_id += 1;
+        self.fill_free_slots();
         id
     }
 
+    // Returns false if nothing changed.
+    pub fn set_spectator(&mut self, player_id: u64, spectator: bool) -> bool {
+        match self.players.get_mut(&player_id) {
+            Some(ref mut player) if player.spectator() != spectator => {
+                player.set_spectator(spectator);
+            },
+            _ => return false,
+        }
+        if spectator {
+            self.stop_playing(player_id);
+        }
+        self.fill_free_slots();
+        // like after dying, so spectating doesn't heal or respawn early
+        if !spectator {
+            if let Some(character_id) = self.players[&player_id].character_id() {
+                self.world.delay_respawn(character_id);
+            }
+        }
+        true
+    }
+
+    // Returns the new name, which gets a number appended if another player already has it.
+    pub fn rename_player(&mut self, player_id: u64, name: String) -> Option<String> {
+        if !self.players.contains_key(&player_id) {
+            return None;
+        }
+        let name = self.unique_name(name, Some(player_id));
+        let player = self.players.get_mut(&player_id).unwrap();
+        player.rename(name.clone());
+        Some(name)
+    }
+
     pub fn remove_player(&mut self, player_id: u64) -> Option<Player> {
         let mut result = self.players.remove(&player_id);
         if let Some(ref mut player) = result {
@@ -56,11 +125,224 @@ impl Model {
         self.players.get(&player_id)
     }
 
+    pub fn players(&self) -> &HashMap<u64, Player> {
+        &self.players
+    }
+
+    pub fn game_mode(&self) -> &GameMode {
+        &self.game_mode
+    }
+
+    pub fn teams(&self) -> &[Team] {
+        &self.teams
+    }
+
+    // Moves the player to another team and respawns its character there.
+    // Returns false if the switch isn't allowed.
+    pub fn switch_team(&mut self, player_id: u64, team: u64) -> bool {
+        let current_team = match self.players.get(&player_id) {
+            Some(player) if player.playing() => player.team(),
+            _ => return false,
+        };
+        if !self.game_mode.can_switch_team(&self.players, &self.teams, current_team, team) {
+            return false;
+        }
+        let player = self.players.get_mut(&player_id).unwrap();
+        player.set_team(Some(team));
+        if let Some(character_id) = player.character_id() {
+            self.world.respawn_character(character_id);
+        }
+        true
+    }
+
+    // Players are hashed in the order of their ids, so it doesn't depend on the HashMap.
+    pub fn checksum(&self) -> u64 {
+        let mut player_ids: Vec<u64> = self.players.keys().cloned().collect();
+        player_ids.sort();
+        let mut hasher = Fnv1a::new();
+        for player_id in player_ids {
+            hasher.add(&player_id);
+            hasher.add(&self.players[&player_id]);
+        }
+        hasher.add(&self.world.checksum());
+        hasher.add(&self.game_mode);
+        hasher.add(&self.teams);
+        hasher.add(&self.next_player_id);
+        hasher.finish()
+    }
+
     pub fn world<'a>(&'a self) -> &'a World {
         &self.world
     }
 
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
     pub fn do_tick(&mut self) {
-        self.world.do_tick();
+        if self.game_mode.phase() != Phase::Intermission {
+            self.world.do_tick();
+        } else {
+            self.world.stand_still();
+        }
+        // kills can also happen outside of the world tick, e.g. by hitscan on the server
+        for kill in self.world.take_kills() {
+            self.score_kill(kill);
+        }
+        if self.game_mode.do_tick(&self.players, &self.teams) == Some(Phase::Playing) {
+            self.start_match();
+        }
+        // slots get free when players leave
+        self.fill_free_slots();
+    }
+
+    // Lets waiting players play in the order they joined, as long as the game mode has room.
+    fn fill_free_slots(&mut self) {
+        let mut waiting_ids: Vec<u64> = self.players.iter()
+            .filter(|&(_, player)| !player.spectator() && !player.playing())
+            .map(|(&player_id, _)| player_id)
+            .collect();
+        waiting_ids.sort();
+        for player_id in waiting_ids {
+            if !self.game_mode.has_free_slot(&self.players) {
+                break;
+            }
+            self.start_playing(player_id);
+        }
+    }
+
+    fn start_playing(&mut self, player_id: u64) {
+        let character_id = self.world.spawn_character();
+        let team = self.game_mode.assign_team(&self.players, &self.teams);
+        let player = self.players.get_mut(&player_id).unwrap();
+        player.set_character_id(Some(character_id));
+        player.set_team(team);
+    }
+
+    fn stop_playing(&mut self, player_id: u64) {
+        let player = self.players.get_mut(&player_id).unwrap();
+        if let Some(character_id) = player.character_id() {
+            self.world.remove_character(character_id);
+        }
+        player.set_character_id(None);
+        player.set_team(None);
+    }
+
+    fn score_kill(&mut self, kill: Kill) {
+        let killer_id = self.player_id_by_character_id(kill.killer);
+        let victim_id = self.player_id_by_character_id(kill.victim);
+        if let Some(victim_id) = victim_id {
+            self.players.get_mut(&victim_id).unwrap().add_death();
+        }
+        let (killer_id, victim_id) = match (killer_id, victim_id) {
+            (Some(killer_id), Some(victim_id)) => (killer_id, victim_id),
+            // the killer may have left already
+            _ => return,
+        };
+        let suicide = killer_id == victim_id;
+        let points = self.game_mode.kill_points(
+            &self.players[&killer_id],
+            &self.players[&victim_id],
+            suicide,
+        );
+        let killer = self.players.get_mut(&killer_id).unwrap();
+        // kills of teammates don't count as kills
+        if points > 0 {
+            killer.add_kill();
+        }
+        killer.add_score(points);
+        if let Some(team) = killer.team().and_then(|team| self.teams.get_mut(team as usize)) {
+            team.add_score(points);
+        }
+    }
+
+    fn unique_name(&self, name: String, player_id: Option<u64>) -> String {
+        let taken = |name: &str| self.players.iter().any(|(&id, player)| {
+            Some(id) != player_id && player.name() == name
+        });
+        if !taken(&name) {
+            return name;
+        }
+        let mut number = 2;
+        loop {
+            // the number has to fit in as well
+            let suffix = format!(" ({})", number);
+            let base_length = MAX_PLAYER_NAME_LENGTH.saturating_sub(suffix.chars().count());
+            let base: String = name.chars().take(base_length).collect();
+            let numbered_name = format!("{}{}", base.trim_end(), suffix);
+            if !taken(&numbered_name) {
+                return numbered_name;
+            }
+            number += 1;
+        }
+    }
+
+    fn start_match(&mut self) {
+        for team in self.teams.iter_mut() {
+            team.reset_score();
+        }
+        // sorted, so everyone gets the same spawn point on all machines
+        let mut player_ids: Vec<u64> = self.players.keys().cloned().collect();
+        player_ids.sort();
+        for player_id in player_ids {
+            let player = self.players.get_mut(&player_id).unwrap();
+            player.reset_stats();
+            if let Some(character_id) = player.character_id() {
+                self.world.respawn_character(character_id);
+            }
+        }
+    }
+
+    fn player_id_by_character_id(&self, character_id: EntityId) -> Option<u64> {
+        self.players.iter()
+            .find(|&(_, player)| player.character_id() == Some(character_id))
+            .map(|(&player_id, _)| player_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::iter;
+
+    fn model() -> Model {
+        Model::new(Arc::new(Level::default()), MatchRules::default())
+    }
+
+    #[test]
+    fn numbered_names_fit_the_length_limit() {
+        let mut model = model();
+        let name: String = iter::repeat('a').take(MAX_PLAYER_NAME_LENGTH).collect();
+        model.add_player(name.clone(), false);
+        let id = model.add_player(name.clone(), false);
+        let numbered_name = model.player(id).unwrap().name();
+        assert_eq!(numbered_name.chars().count(), MAX_PLAYER_NAME_LENGTH);
+        assert!(numbered_name.ends_with(" (2)"));
+    }
+
+    #[test]
+    fn checksum_does_not_depend_on_player_order() {
+        let mut a = model();
+        let mut b = model();
+        for player_id in 0..8 {
+            a.players.insert(player_id, Player::new(format!("player {}", player_id)));
+        }
+        for player_id in (0..8).rev() {
+            b.players.insert(player_id, Player::new(format!("player {}", player_id)));
+        }
+        a.next_player_id = 8;
+        b.next_player_id = 8;
+        assert_eq!(a.checksum(), b.checksum());
+    }
+
+    #[test]
+    fn spectating_does_not_respawn_early() {
+        let mut model = model();
+        let id = model.add_player(String::from("a"), false);
+        assert!(model.set_spectator(id, true));
+        assert!(model.set_spectator(id, false));
+        let character_id = model.player(id).unwrap().character_id().unwrap();
+        assert!(!model.world().character(character_id).unwrap().alive());
     }
 }
\ No newline at end of file
@@ -0,0 +1,233 @@
+use consts::TICK_SPEED;
+use math::FixedPoint;
+use tick_time::TickRate;
+
+// keeps fixed-point products from overflowing
+const MAX_CONFIG_VALUE: f64 = 10000.0;
+
+// Tunes how characters move, in meters and seconds.
+#[derive(Debug, Clone, Copy)]
+pub struct MovementSettings {
+    pub ground_acceleration: f64,
+    // ground friction is chosen so walking converges to this speed
+    pub max_walking_speed: f64,
+    pub air_acceleration: f64,
+    // in the air, velocity decays by a factor of e^air_drag per second
+    pub air_drag: f64,
+    pub jump_velocity: f64,
+    pub gravity: f64,
+    // from the feet to the eyes
+    pub character_height: f64,
+    pub character_radius: f64,
+    // how far crouching lowers the eyes
+    pub crouch_depth: f64,
+    pub crouch_acceleration: f64,
+    // like the air drag, for the crouching speed
+    pub crouch_drag: f64,
+    pub aim_acceleration_factor: f64,
+}
+
+impl Default for MovementSettings {
+    fn default() -> MovementSettings {
+        MovementSettings {
+            ground_acceleration: 180.0,
+            max_walking_speed: 6.0,
+            air_acceleration: 6.0,
+            air_drag: 1.206,
+            jump_velocity: 4.0,
+            gravity: 10.0,
+            character_height: 1.7,
+            character_radius: 0.3,
+            crouch_depth: 0.8,
+            crouch_acceleration: 144.0,
+            crouch_drag: 26.78,
+            aim_acceleration_factor: 0.5,
+        }
+    }
+}
+
+// The movement settings converted to the tick rate the simulation runs at.
+// The server sends them to the clients on connect, so predictions use the exact same values.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct MovementParams {
+    pub ground_acceleration: FixedPoint,
+    pub max_walking_speed: FixedPoint,
+    pub air_acceleration: FixedPoint,
+    // velocity is divided by this every tick in the air
+    pub air_friction: FixedPoint,
+    pub jump_velocity: FixedPoint,
+    pub gravity: FixedPoint,
+    pub character_height: FixedPoint,
+    pub character_radius: FixedPoint,
+    pub crouch_depth: FixedPoint,
+    pub crouch_acceleration: FixedPoint,
+    pub crouch_friction: FixedPoint,
+    pub aim_acceleration_factor: FixedPoint,
+}
+
+impl MovementParams {
+    pub fn new(settings: &MovementSettings, tick_rate: TickRate) -> MovementParams {
+        let per_second = tick_rate.per_second() as f64;
+        MovementParams {
+            ground_acceleration: tick_rate.per_tick_squared(settings.ground_acceleration),
+            max_walking_speed: tick_rate.per_tick(settings.max_walking_speed),
+            air_acceleration: tick_rate.per_tick_squared(settings.air_acceleration),
+            air_friction: FixedPoint::from_float((settings.air_drag / per_second).exp()),
+            jump_velocity: tick_rate.per_tick(settings.jump_velocity),
+            gravity: tick_rate.per_tick_squared(settings.gravity),
+            character_height: FixedPoint::from_float(settings.character_height),
+            character_radius: FixedPoint::from_float(settings.character_radius),
+            crouch_depth: FixedPoint::from_float(settings.crouch_depth),
+            crouch_acceleration: tick_rate.per_tick_squared(settings.crouch_acceleration),
+            crouch_friction: FixedPoint::from_float((settings.crouch_drag / per_second).exp()),
+            aim_acceleration_factor: FixedPoint::from_float(settings.aim_acceleration_factor),
+        }
+    }
+
+    pub fn ground_friction(&self) -> FixedPoint {
+        FixedPoint::one() + self.ground_acceleration / self.max_walking_speed
+    }
+}
+
+impl Default for MovementParams {
+    fn default() -> MovementParams {
+        MovementParams::new(&MovementSettings::default(), TICK_SPEED)
+    }
+}
+
+// Overrides movement settings in the server config and in maps.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MovementConfig {
+    pub ground_acceleration: Option<f64>,
+    pub max_walking_speed: Option<f64>,
+    pub air_acceleration: Option<f64>,
+    pub air_drag: Option<f64>,
+    pub jump_velocity: Option<f64>,
+    pub gravity: Option<f64>,
+    pub character_height: Option<f64>,
+    pub character_radius: Option<f64>,
+    pub crouch_depth: Option<f64>,
+    pub crouch_acceleration: Option<f64>,
+    pub crouch_drag: Option<f64>,
+    pub aim_acceleration_factor: Option<f64>,
+}
+
+impl MovementConfig {
+    // Returns the given settings with the configured values replaced.
+    pub fn apply(&self, settings: &MovementSettings) -> Result<MovementSettings, String> {
+        let mut settings = *settings;
+        apply_value(&mut settings.ground_acceleration, self.ground_acceleration,
+                    0.0, "ground_acceleration")?;
+        apply_value(&mut settings.max_walking_speed, self.max_walking_speed,
+                    0.1, "max_walking_speed")?;
+        apply_value(&mut settings.air_acceleration, self.air_acceleration,
+                    0.0, "air_acceleration")?;
+        apply_value(&mut settings.air_drag, self.air_drag, 0.0, "air_drag")?;
+        apply_value(&mut settings.jump_velocity, self.jump_velocity, 0.0, "jump_velocity")?;
+        apply_value(&mut settings.gravity, self.gravity, 0.0, "gravity")?;
+        apply_value(&mut settings.character_height, self.character_height,
+                    0.1, "character_height")?;
+        apply_value(&mut settings.character_radius, self.character_radius,
+                    0.1, "character_radius")?;
+        apply_value(&mut settings.crouch_depth, self.crouch_depth, 0.0, "crouch_depth")?;
+        apply_value(&mut settings.crouch_acceleration, self.crouch_acceleration,
+                    0.0, "crouch_acceleration")?;
+        apply_value(&mut settings.crouch_drag, self.crouch_drag, 0.0, "crouch_drag")?;
+        apply_value(&mut settings.aim_acceleration_factor, self.aim_acceleration_factor,
+                    0.0, "aim_acceleration_factor")?;
+        if settings.crouch_depth >= settings.character_height {
+            return Err(String::from("Crouch depth must be less than the character height!"));
+        }
+        Ok(settings)
+    }
+}
+
+fn apply_value(setting: &mut f64, value: Option<f64>, min: f64, name: &str)
+-> Result<(), String> {
+    if let Some(value) = value {
+        if !value.is_finite() || value < min || value > MAX_CONFIG_VALUE {
+            return Err(format!("Movement parameter {} = {} is out of range!", name, value));
+        }
+        *setting = value;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use math::Vec3;
+    use math::FPAngle;
+    use model::world::level::Level;
+    use model::world::level::SpawnPoint;
+    use model::world::character::Character;
+    use model::world::character::CharacterInput;
+
+    const TICK_RATES: [u64; 3] = [60, 120, 128];
+
+    fn spawn(tick_rate: TickRate) -> (Level, MovementParams, Character) {
+        let level = Level::default();
+        let params = MovementParams::new(&MovementSettings::default(), tick_rate);
+        let spawn_point = SpawnPoint { pos: Vec3::zero(), yaw: FPAngle::zero() };
+        let mut character = Character::new(spawn_point, &params);
+        // land on the floor
+        for _ in 0..tick_rate.per_second() {
+            character.do_tick(&level, &params);
+        }
+        assert!(character.grounded());
+        (level, params, character)
+    }
+
+    fn jump_height(tick_rate: TickRate) -> f64 {
+        let (level, params, mut character) = spawn(tick_rate);
+        let start: f64 = character.pos().z.into();
+        let mut input = CharacterInput::default();
+        input.num_jumps = 1;
+        character.set_input(input);
+        let mut max_height = start;
+        for _ in 0..tick_rate.per_second() * 2 {
+            character.do_tick(&level, &params);
+            max_height = max_height.max(character.pos().z.into());
+        }
+        max_height - start
+    }
+
+    fn walking_speed(tick_rate: TickRate) -> f64 {
+        let (level, params, mut character) = spawn(tick_rate);
+        let mut input = CharacterInput::default();
+        input.forward = true;
+        character.set_input(input);
+        for _ in 0..tick_rate.per_second() * 2 {
+            character.do_tick(&level, &params);
+        }
+        let before: f64 = character.pos().x.into();
+        for _ in 0..tick_rate.per_second() {
+            character.do_tick(&level, &params);
+        }
+        let after: f64 = character.pos().x.into();
+        after - before
+    }
+
+    #[test]
+    fn same_jump_height_at_all_tick_rates() {
+        let expected = jump_height(TICK_SPEED);
+        assert!(expected > 0.5);
+        for &per_second in TICK_RATES.iter() {
+            let height = jump_height(TickRate::from_per_second(per_second));
+            assert!((height - expected).abs() < expected * 0.03,
+                    "jump height at {} Hz: {}, expected {}", per_second, height, expected);
+        }
+    }
+
+    #[test]
+    fn same_walking_speed_at_all_tick_rates() {
+        let expected = MovementSettings::default().max_walking_speed;
+        for &per_second in TICK_RATES.iter() {
+            let speed = walking_speed(TickRate::from_per_second(per_second));
+            assert!((speed - expected).abs() < expected * 0.01,
+                    "walking speed at {} Hz: {}, expected {}", per_second, speed, expected);
+        }
+    }
+}
\ No newline at end of file
@@ -1,7 +1,17 @@
+use consts::MAX_PLAYER_NAME_LENGTH;
+use model::world::entity::EntityId;
+use util;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Player {
     name: String,
-    character_id: Option<u64>,
+    // players without a character are spectating, either by choice or waiting for a free slot
+    character_id: Option<EntityId>,
+    spectator: bool,
+    team: Option<u64>,
+    score: i64,
+    kills: u64,
+    deaths: u64,
 }
 
 impl Player {
@@ -9,6 +19,11 @@ impl Player {
         Player {
             name,
             character_id: None,
+            spectator: false,
+            team: None,
+            score: 0,
+            kills: 0,
+            deaths: 0,
         }
     }
 
@@ -16,19 +31,74 @@ impl Player {
         self.name = new_name;
     }
 
-    pub fn name(&mut self) -> &str {
+    pub fn name(&self) -> &str {
         self.name.as_str()
     }
 
-    pub fn set_character_id(&mut self, character_id: Option<u64>) {
+    pub fn set_character_id(&mut self, character_id: Option<EntityId>) {
         self.character_id = character_id;
     }
 
-    pub fn character_id(&self) -> Option<u64> {
+    pub fn character_id(&self) -> Option<EntityId> {
         self.character_id
     }
 
+    pub fn playing(&self) -> bool {
+        self.character_id.is_some()
+    }
+
+    // whether the player chose to spectate
+    pub fn spectator(&self) -> bool {
+        self.spectator
+    }
+
+    pub fn set_spectator(&mut self, spectator: bool) {
+        self.spectator = spectator;
+    }
+
+    pub fn team(&self) -> Option<u64> {
+        self.team
+    }
+
+    pub fn set_team(&mut self, team: Option<u64>) {
+        self.team = team;
+    }
+
+    pub fn score(&self) -> i64 {
+        self.score
+    }
+
+    pub fn kills(&self) -> u64 {
+        self.kills
+    }
+
+    pub fn deaths(&self) -> u64 {
+        self.deaths
+    }
+
+    pub fn add_score(&mut self, points: i64) {
+        self.score += points;
+    }
+
+    pub fn add_kill(&mut self) {
+        self.kills += 1;
+    }
+
+    pub fn add_death(&mut self) {
+        self.deaths += 1;
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.score = 0;
+        self.kills = 0;
+        self.deaths = 0;
+    }
+
     pub fn take_name(self) -> String {
         self.name
     }
-}
\ No newline at end of file
+}
+
+pub fn sanitize_name(name: &str) -> Option<String> {
+    util::sanitize_text(name, MAX_PLAYER_NAME_LENGTH)
+}
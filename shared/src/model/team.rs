@@ -0,0 +1,44 @@
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Team {
+    name: String,
+    color: [f32; 3],
+    score: i64,
+}
+
+impl Team {
+    pub fn new(name: String, color: [f32; 3]) -> Team {
+        Team {
+            name,
+            color,
+            score: 0,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn color(&self) -> [f32; 3] {
+        self.color
+    }
+
+    pub fn score(&self) -> i64 {
+        self.score
+    }
+
+    pub fn add_score(&mut self, points: i64) {
+        self.score += points;
+    }
+
+    pub fn reset_score(&mut self) {
+        self.score = 0;
+    }
+}
+
+// The teams of team based game modes, the index in the list is the team id.
+pub fn default_teams() -> Vec<Team> {
+    vec![
+        Team::new(String::from("Red"), [0.9, 0.2, 0.2]),
+        Team::new(String::from("Blue"), [0.2, 0.3, 0.9]),
+    ]
+}
\ No newline at end of file
@@ -1,6 +1,25 @@
+use consts::MAX_HEALTH;
+use consts::SPAWN_ARMOR;
+use consts::ARMOR_ABSORPTION;
+use consts::MAX_ARMOR;
+use consts::TICK_SPEED;
+use consts::RIFLE_FIRE_INTERVAL;
+use consts::ROCKET_LAUNCHER_FIRE_INTERVAL;
+use consts::GRENADE_LAUNCHER_FIRE_INTERVAL;
+use consts::MAX_ROCKETS;
+use consts::MAX_GRENADES;
+use consts::ROCKET_PICKUP_AMMO;
+use consts::GRENADE_PICKUP_AMMO;
 use math::FixedPoint;
 use math::FPAngle;
 use math::Vec3;
+use model::world::level::Level;
+use model::world::level::Body;
+use model::world::level::Ray;
+use model::world::level::SpawnPoint;
+use model::world::projectile::ProjectileKind;
+use model::world::pickup::PickupKind;
+use model::movement_params::MovementParams;
 
 #[derive(Debug, Default, Copy, Clone, Serialize, Deserialize)]
 pub struct ViewDir {
@@ -26,6 +45,116 @@ impl ViewDir {
     pub fn pitch(&self) -> FPAngle {
         self.pitch
     }
+
+    // whether the angles are in the ranges add_yaw and add_pitch keep them in
+    pub fn is_normalized(&self) -> bool {
+        let q = FPAngle::quarter();
+        self.yaw >= FPAngle::zero() && self.yaw < FPAngle::whole()
+            && self.pitch >= -q && self.pitch <= q
+    }
+
+    pub fn normalized(&self) -> ViewDir {
+        let mut view_dir = ViewDir::default();
+        view_dir.add_yaw(self.yaw);
+        view_dir.add_pitch(self.pitch);
+        view_dir
+    }
+
+    // the yaw and pitch to add to get to other, the yaw goes the short way around
+    pub fn delta(&self, other: &ViewDir) -> (FPAngle, FPAngle) {
+        let w = FPAngle::whole();
+        let mut yaw = ((other.yaw - self.yaw) % w + w) % w;
+        if yaw > FPAngle::half() {
+            yaw = yaw - w;
+        }
+        (yaw, other.pitch - self.pitch)
+    }
+
+    // unit vector, positive pitch looks up
+    pub fn direction(&self) -> Vec3 {
+        let pc = self.pitch.cos();
+        Vec3::new(pc * self.yaw.cos(), pc * self.yaw.sin(), self.pitch.sin())
+    }
+}
+
+const NUM_WEAPONS: usize = 3;
+
+pub const WEAPONS: [Weapon; NUM_WEAPONS] =
+    [Weapon::Rifle, Weapon::RocketLauncher, Weapon::GrenadeLauncher];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Weapon {
+    Rifle,
+    RocketLauncher,
+    GrenadeLauncher,
+}
+
+impl Weapon {
+    fn index(self) -> usize {
+        match self {
+            Weapon::Rifle => 0,
+            Weapon::RocketLauncher => 1,
+            Weapon::GrenadeLauncher => 2,
+        }
+    }
+
+    pub fn next(self) -> Weapon {
+        match self {
+            Weapon::Rifle => Weapon::RocketLauncher,
+            Weapon::RocketLauncher => Weapon::GrenadeLauncher,
+            Weapon::GrenadeLauncher => Weapon::Rifle,
+        }
+    }
+
+    pub fn prev(self) -> Weapon {
+        match self {
+            Weapon::Rifle => Weapon::GrenadeLauncher,
+            Weapon::RocketLauncher => Weapon::Rifle,
+            Weapon::GrenadeLauncher => Weapon::RocketLauncher,
+        }
+    }
+
+    // in ticks
+    pub fn fire_interval(self) -> u64 {
+        match self {
+            Weapon::Rifle => TICK_SPEED.ticks(RIFLE_FIRE_INTERVAL),
+            Weapon::RocketLauncher => TICK_SPEED.ticks(ROCKET_LAUNCHER_FIRE_INTERVAL),
+            Weapon::GrenadeLauncher => TICK_SPEED.ticks(GRENADE_LAUNCHER_FIRE_INTERVAL),
+        }
+    }
+
+    // None for hitscan weapons
+    pub fn projectile(self) -> Option<ProjectileKind> {
+        match self {
+            Weapon::Rifle => None,
+            Weapon::RocketLauncher => Some(ProjectileKind::Rocket),
+            Weapon::GrenadeLauncher => Some(ProjectileKind::Grenade),
+        }
+    }
+
+    // None for weapons with unlimited ammo
+    pub fn max_ammo(self) -> Option<u64> {
+        match self {
+            Weapon::Rifle => None,
+            Weapon::RocketLauncher => Some(MAX_ROCKETS),
+            Weapon::GrenadeLauncher => Some(MAX_GRENADES),
+        }
+    }
+
+    // given by ammo and weapon pickups
+    pub fn pickup_ammo(self) -> u64 {
+        match self {
+            Weapon::Rifle => 0,
+            Weapon::RocketLauncher => ROCKET_PICKUP_AMMO,
+            Weapon::GrenadeLauncher => GRENADE_PICKUP_AMMO,
+        }
+    }
+}
+
+impl Default for Weapon {
+    fn default() -> Weapon {
+        Weapon::Rifle
+    }
 }
 
 #[derive(Debug, Default, Copy, Clone, Serialize, Deserialize)]
@@ -36,6 +165,9 @@ pub struct CharacterInput {
     pub left: bool,
     pub crouch: bool,
     pub num_jumps: u64,
+    pub fire: bool,
+    pub aim: bool,
+    pub weapon: Weapon,
     pub view_dir: ViewDir,
 }
 
@@ -47,33 +179,76 @@ pub struct Character {
     vel: Vec3,
     view_dir: ViewDir,
     jumping: bool,
+    grounded: bool,
     expansion: FixedPoint,
     expansion_speed: FixedPoint,
+    // added to the yaw of the input, so spawn points and teleporters can turn characters
+    yaw_offset: FPAngle,
+    // ticks until the next shot can be fired
+    fire_cooldown: u64,
+    // the weapon fired in the last tick
+    fired: Option<Weapon>,
+    // the character is dead when this reaches zero
+    health: u64,
+    armor: u64,
+    // indexed by weapon, characters spawn with the rifle only
+    weapons: [bool; NUM_WEAPONS],
+    ammo: [u64; NUM_WEAPONS],
+    // ticks until a dead character respawns
+    respawn_delay: u64,
+    // taken from the movement params on spawn
+    height: FixedPoint,
+    radius: FixedPoint,
 }
 
 impl Character {
-    pub fn new() -> Character {
-        let character_height = FixedPoint::fraction(17, 10); // TODO use const in consts instead
-        Character {
+    pub fn new(spawn_point: SpawnPoint, params: &MovementParams) -> Character {
+        let mut pos = spawn_point.pos;
+        pos.z += params.character_height;
+        let mut character = Character {
             input: Default::default(),
             old_input: Default::default(),
-            pos: Vec3::new(
-                FixedPoint::zero(),
-                FixedPoint::zero(),
-                character_height,
-            ),
+            pos,
             vel: Vec3::zero(),
-            view_dir: Default::default(),
+            view_dir: ViewDir::default(),
             jumping: false,
+            grounded: true,
             expansion: FixedPoint::zero(),
             expansion_speed: FixedPoint::zero(),
-        }
+            yaw_offset: FPAngle::zero(),
+            fire_cooldown: 0,
+            fired: None,
+            health: MAX_HEALTH,
+            armor: SPAWN_ARMOR,
+            weapons: [true, false, false],
+            ammo: [0; NUM_WEAPONS],
+            respawn_delay: 0,
+            height: params.character_height,
+            radius: params.character_radius,
+        };
+        character.face(spawn_point.yaw);
+        character
     }
 
     pub fn set_input(&mut self, input: CharacterInput) {
         self.input = input;
     }
 
+    pub fn input(&self) -> CharacterInput {
+        self.input
+    }
+
+    // Continues with the input of a replaced character,
+    // inputs count jumps over the whole game, so they must not start over.
+    pub fn keep_input(&mut self, input: CharacterInput) {
+        let yaw = self.view_dir.yaw();
+        self.input = input;
+        self.old_input = input;
+        self.view_dir = input.view_dir;
+        self.yaw_offset = FPAngle::zero();
+        self.face(yaw);
+    }
+
     pub fn pos(&self) -> Vec3 {
         self.pos
     }
@@ -82,19 +257,141 @@ impl Character {
         self.view_dir
     }
 
-    pub fn do_tick(&mut self) {
-        // TODO move these to consts
+    pub fn yaw_offset(&self) -> FPAngle {
+        self.yaw_offset
+    }
+
+    pub fn health(&self) -> u64 {
+        self.health
+    }
+
+    pub fn armor(&self) -> u64 {
+        self.armor
+    }
+
+    pub fn alive(&self) -> bool {
+        self.health > 0
+    }
+
+    pub fn has_weapon(&self, weapon: Weapon) -> bool {
+        self.weapons[weapon.index()]
+    }
+
+    // None for weapons with unlimited ammo
+    pub fn ammo(&self, weapon: Weapon) -> Option<u64> {
+        weapon.max_ammo().map(|_| self.ammo[weapon.index()])
+    }
+
+    // Returns whether the pickup was of any use, otherwise it stays where it is.
+    pub fn pick_up(&mut self, kind: PickupKind) -> bool {
+        if !self.alive() {
+            return false;
+        }
+        let amount = kind.params().amount;
+        match kind {
+            PickupKind::Health => add_capped(&mut self.health, amount, MAX_HEALTH),
+            PickupKind::Armor => add_capped(&mut self.armor, amount, MAX_ARMOR),
+            PickupKind::Ammo(weapon) => self.add_ammo(weapon, amount),
+            PickupKind::Weapon(weapon) => {
+                let new_weapon = !self.has_weapon(weapon);
+                self.weapons[weapon.index()] = true;
+                self.add_ammo(weapon, amount) || new_weapon
+            },
+        }
+    }
+
+    fn add_ammo(&mut self, weapon: Weapon, amount: u64) -> bool {
+        match weapon.max_ammo() {
+            Some(max_ammo) => add_capped(&mut self.ammo[weapon.index()], amount, max_ammo),
+            None => false,
+        }
+    }
+
+    fn can_fire(&self, weapon: Weapon) -> bool {
+        self.has_weapon(weapon) && self.ammo(weapon).map(|ammo| ammo > 0).unwrap_or(true)
+    }
+
+    // The armor takes its share of the damage as long as it lasts.
+    // Returns whether the character died.
+    pub fn damage(&mut self, damage: u64, respawn_delay: u64) -> bool {
+        if !self.alive() {
+            return false;
+        }
+        let (nominator, denominator) = ARMOR_ABSORPTION;
+        let absorbed = (damage * nominator / denominator).min(self.armor);
+        self.armor -= absorbed;
+        self.health = self.health.saturating_sub(damage - absorbed);
+        if self.alive() {
+            return false;
+        }
+        self.die(respawn_delay);
+        true
+    }
+
+    // Ignores the armor, returns whether the character was alive.
+    pub fn kill(&mut self, respawn_delay: u64) -> bool {
+        if !self.alive() {
+            return false;
+        }
+        self.health = 0;
+        self.die(respawn_delay);
+        true
+    }
+
+    fn die(&mut self, respawn_delay: u64) {
+        self.vel = Vec3::zero();
+        self.fired = None;
+        self.respawn_delay = respawn_delay;
+    }
+
+    // Moves the feet to the destination.
+    pub fn teleport(&mut self, destination: Vec3, yaw: Option<FPAngle>, keep_velocity: bool) {
+        self.pos = destination;
+        self.pos.z += self.current_height();
+        if !keep_velocity {
+            self.vel = Vec3::zero();
+        }
+        if let Some(yaw) = yaw {
+            self.face(yaw);
+        }
+    }
+
+    // The view dir follows the input, so the turn is kept as an offset to it.
+    fn face(&mut self, yaw: FPAngle) {
+        let w = FPAngle::whole();
+        let delta = yaw - self.view_dir.yaw();
+        self.yaw_offset = ((self.yaw_offset + delta) % w + w) % w;
+        self.view_dir.add_yaw(delta);
+    }
+
+    pub fn launch(&mut self, velocity: Vec3) {
+        self.vel = velocity;
+        self.grounded = false;
+        self.jumping = false;
+    }
+
+    // Counts down the respawn delay of a dead character.
+    // Returns whether it is time to respawn.
+    pub fn tick_respawn_delay(&mut self) -> bool {
+        if self.respawn_delay > 0 {
+            self.respawn_delay -= 1;
+        }
+        self.respawn_delay == 0
+    }
+
+    pub fn do_tick(&mut self, level: &Level, params: &MovementParams) {
         let max_expansion = FixedPoint::new(0);
-        let min_expansion = FixedPoint::fraction(-8, 10);
-        let ground_acceleration = FixedPoint::fraction(1, 80);
-        let max_walking_speed = FixedPoint::fraction(1, 20);
-        let ground_friction = FixedPoint::one() + ground_acceleration / max_walking_speed;
-        let air_acceleration = FixedPoint::fraction(1, 2400);
-        let air_friction = FixedPoint::fraction(100, 99);
-        let max_expansion_acceleration = FixedPoint::fraction(1, 100);
-        let jump_velocity = FixedPoint::fraction(1, 30);
-        let expansion_friction = FixedPoint::fraction(10, 8);
-        let gravity = FixedPoint::fraction(1, 1440);
+        let min_expansion = -params.crouch_depth;
+        let ground_acceleration = params.ground_acceleration;
+        let max_walking_speed = params.max_walking_speed;
+        let ground_friction = params.ground_friction();
+        let air_acceleration = params.air_acceleration;
+        let air_friction = params.air_friction;
+        let max_expansion_acceleration = params.crouch_acceleration;
+        let jump_velocity = params.jump_velocity;
+        let expansion_friction = params.crouch_friction;
+        let gravity = params.gravity;
+        let aim_acceleration_factor = params.aim_acceleration_factor;
 
         // calculate move direction
         let mut input_acceleration = Vec3::zero();
@@ -133,6 +430,9 @@ impl Character {
             } else {
                 input_acceleration.scale_to(air_acceleration)
             };
+            if self.input.aim {
+                input_acceleration = input_acceleration * aim_acceleration_factor;
+            }
         }
 
         // apply acceleration
@@ -176,25 +476,141 @@ impl Character {
         self.expansion += self.expansion_speed;
         self.pos += self.vel + exp_speed;
 
-        // cap position
-        if self.pos.z < self.current_height() {
-            self.pos.z = self.current_height();
-            self.vel.z = FixedPoint::zero();
+        // collide with level
+        let was_grounded = self.grounded;
+        let mut body = self.body();
+        self.grounded = level.collide(&mut body, &mut self.vel);
+        if !self.grounded && was_grounded && !self.vel.z.is_positive() {
+            // stick to the ground when walking down slopes
+            let mut probe = body;
+            probe.feet.z -= max_walking_speed;
+            let mut probe_vel = self.vel;
+            if level.collide(&mut probe, &mut probe_vel) {
+                body = probe;
+                self.vel = probe_vel;
+                self.grounded = true;
+            }
         }
+        self.pos = body.feet;
+        self.pos.z += body.height;
 
         // apply view dir
         self.view_dir = self.input.view_dir;
+        self.view_dir.add_yaw(self.yaw_offset);
+
+        // fire
+        self.fired = None;
+        if self.fire_cooldown > 0 {
+            self.fire_cooldown -= 1;
+        } else if self.input.fire && self.can_fire(self.input.weapon) {
+            let weapon = self.input.weapon;
+            if weapon.max_ammo().is_some() {
+                self.ammo[weapon.index()] -= 1;
+            }
+            self.fired = Some(weapon);
+            self.fire_cooldown = weapon.fire_interval();
+        }
 
         // reset flags
         self.old_input = self.input;
     }
 
-    fn grounded(&self) -> bool {
-        (self.pos.z - self.current_height()).is_zero()
+    pub fn grounded(&self) -> bool {
+        self.grounded
+    }
+
+    // The hitscan shot fired in the last tick, from the eyes in view direction.
+    pub fn shot(&self) -> Option<Ray> {
+        match self.fired {
+            Some(weapon) if weapon.projectile().is_none() => Some(self.aim_ray()),
+            _ => None,
+        }
+    }
+
+    // For ticks in which the character doesn't tick.
+    pub fn clear_shot(&mut self) {
+        self.fired = None;
+    }
+
+    // The projectile launched in the last tick.
+    pub fn launched_projectile(&self) -> Option<(ProjectileKind, Ray)> {
+        self.fired
+            .and_then(|weapon| weapon.projectile())
+            .map(|kind| (kind, self.aim_ray()))
+    }
+
+    fn aim_ray(&self) -> Ray {
+        Ray {
+            origin: self.pos,
+            dir: self.view_dir.direction(),
+        }
+    }
+
+    // Moves the character, stops it from moving against the normal
+    // and makes sure it doesn't end up inside the level.
+    pub fn push(&mut self, offset: Vec3, normal: Vec3, level: &Level) {
+        self.pos += offset;
+        let normal_vel = self.vel.dot(normal);
+        if normal_vel.is_negative() {
+            self.vel -= normal * normal_vel;
+        }
+        let mut body = self.body();
+        if level.collide(&mut body, &mut self.vel) {
+            self.grounded = true;
+        }
+        self.pos = body.feet;
+        self.pos.z += body.height;
+    }
+
+    pub fn body(&self) -> Body {
+        let height = self.current_height();
+        let mut feet = self.pos;
+        feet.z -= height;
+        Body {
+            feet,
+            radius: self.radius,
+            height,
+        }
     }
 
     fn current_height(&self) -> FixedPoint {
-        let character_height = FixedPoint::fraction(17, 10); // TODO use const in consts instead
-        character_height + self.expansion
+        self.height + self.expansion
+    }
+}
+
+// Returns whether anything was added.
+fn add_capped(value: &mut u64, amount: u64, max: u64) -> bool {
+    if *value >= max {
+        return false;
+    }
+    *value = (*value + amount).min(max);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use consts::TICK_SPEED;
+
+    #[test]
+    fn respawning_keeps_the_jump_count() {
+        let level = Level::default();
+        let params = MovementParams::default();
+        let spawn_point = SpawnPoint { pos: Vec3::zero(), yaw: FPAngle::zero() };
+        let mut input = CharacterInput::default();
+        input.num_jumps = 3;
+        let mut character = Character::new(spawn_point, &params);
+        character.set_input(input);
+        let mut respawned = Character::new(spawn_point, &params);
+        respawned.keep_input(character.input());
+        // the client keeps sending its jump count
+        let start: f64 = respawned.pos().z.into();
+        for _ in 0..TICK_SPEED.per_second() {
+            respawned.set_input(input);
+            respawned.do_tick(&level, &params);
+            let height: f64 = respawned.pos().z.into();
+            assert!(height <= start);
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,158 @@
+use std::fmt;
+
+use model::world::character::Character;
+use model::world::projectile::Projectile;
+
+// The generation is increased whenever a slot is freed,
+// so ids of removed entities never refer to the entities that replace them.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct EntityId {
+    index: u32,
+    generation: u32,
+}
+
+impl fmt::Display for EntityId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}v{}", self.index, self.generation)
+    }
+}
+
+impl fmt::Debug for EntityId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Entity {
+    Character(Character),
+    Projectile(Projectile),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Slot {
+    generation: u32,
+    entity: Option<Entity>,
+}
+
+// Everything that lives in a world.
+// Iteration goes by index and freed slots are reused in a fixed order,
+// so the server and client predictions assign the same ids.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Entities {
+    slots: Vec<Slot>,
+    free_indices: Vec<u32>,
+}
+
+impl Entities {
+    pub fn insert(&mut self, entity: Entity) -> EntityId {
+        match self.free_indices.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.entity = Some(entity);
+                EntityId {
+                    index,
+                    generation: slot.generation,
+                }
+            },
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    entity: Some(entity),
+                });
+                EntityId {
+                    index: self.slots.len() as u32 - 1,
+                    generation: 0,
+                }
+            },
+        }
+    }
+
+    pub fn remove(&mut self, id: EntityId) -> Option<Entity> {
+        let entity = {
+            let slot = self.slot_mut(id)?;
+            slot.generation += 1;
+            slot.entity.take()
+        };
+        self.free_indices.push(id.index);
+        entity
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<&Entity> {
+        match self.slots.get(id.index as usize) {
+            Some(slot) if slot.generation == id.generation => slot.entity.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut Entity> {
+        self.slot_mut(id).and_then(|slot| slot.entity.as_mut())
+    }
+
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (EntityId, &'a Entity)> + 'a {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            slot.entity.as_ref().map(|entity| (
+                EntityId {
+                    index: index as u32,
+                    generation: slot.generation,
+                },
+                entity,
+            ))
+        })
+    }
+
+    pub fn character(&self, id: EntityId) -> Option<&Character> {
+        match self.get(id) {
+            Some(&Entity::Character(ref character)) => Some(character),
+            _ => None,
+        }
+    }
+
+    pub fn character_mut(&mut self, id: EntityId) -> Option<&mut Character> {
+        match self.get_mut(id) {
+            Some(&mut Entity::Character(ref mut character)) => Some(character),
+            _ => None,
+        }
+    }
+
+    pub fn characters<'a>(&'a self) -> impl Iterator<Item = (EntityId, &'a Character)> + 'a {
+        self.iter().filter_map(|(id, entity)| match *entity {
+            Entity::Character(ref character) => Some((id, character)),
+            _ => None,
+        })
+    }
+
+    pub fn projectile(&self, id: EntityId) -> Option<&Projectile> {
+        match self.get(id) {
+            Some(&Entity::Projectile(ref projectile)) => Some(projectile),
+            _ => None,
+        }
+    }
+
+    pub fn projectile_mut(&mut self, id: EntityId) -> Option<&mut Projectile> {
+        match self.get_mut(id) {
+            Some(&mut Entity::Projectile(ref mut projectile)) => Some(projectile),
+            _ => None,
+        }
+    }
+
+    pub fn projectiles<'a>(&'a self) -> impl Iterator<Item = (EntityId, &'a Projectile)> + 'a {
+        self.iter().filter_map(|(id, entity)| match *entity {
+            Entity::Projectile(ref projectile) => Some((id, projectile)),
+            _ => None,
+        })
+    }
+
+    fn slot_mut(&mut self, id: EntityId) -> Option<&mut Slot> {
+        match self.slots.get_mut(id.index as usize) {
+            Some(slot) => {
+                if slot.generation == id.generation && slot.entity.is_some() {
+                    Some(slot)
+                } else {
+                    None
+                }
+            },
+            None => None,
+        }
+    }
+}
\ No newline at end of file
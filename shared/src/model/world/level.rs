@@ -0,0 +1,420 @@
+use math::FixedPoint;
+use math::FPAngle;
+use math::Vec3;
+use model::world::pickup::PickupSpawn;
+use model::world::trigger::Trigger;
+
+// how often overlapping shapes are resolved per tick
+const COLLISION_ITERATIONS: usize = 4;
+
+// A vertical, axis-aligned box standing on its feet position.
+#[derive(Debug, Clone, Copy)]
+pub struct Body {
+    pub feet: Vec3,
+    pub radius: FixedPoint,
+    pub height: FixedPoint,
+}
+
+impl Body {
+    // Returns where the ray enters the body.
+    pub fn raycast(&self, ray: &Ray, max_distance: FixedPoint) -> Option<RayHit> {
+        let mut interval = Interval::new(max_distance);
+        interval.clip_box(ray.origin, ray.dir, self.min(), self.max());
+        interval.entry()
+    }
+
+    // The point of the body closest to the given point.
+    pub fn closest_point(&self, point: Vec3) -> Vec3 {
+        let min = self.min();
+        let max = self.max();
+        Vec3::new(
+            point.x.max(min.x).min(max.x),
+            point.y.max(min.y).min(max.y),
+            point.z.max(min.z).min(max.z),
+        )
+    }
+
+    pub fn min(&self) -> Vec3 {
+        Vec3::new(self.feet.x - self.radius, self.feet.y - self.radius, self.feet.z)
+    }
+
+    pub fn max(&self) -> Vec3 {
+        Vec3::new(self.feet.x + self.radius, self.feet.y + self.radius, self.feet.z + self.height)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Ray {
+    pub origin: Vec3,
+    // unit length
+    pub dir: Vec3,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RayHit {
+    pub distance: FixedPoint,
+    // unit length, zero if the ray starts inside
+    pub normal: Vec3,
+}
+
+// The distances along a ray that are inside a convex shape.
+struct Interval {
+    min: FixedPoint,
+    max: FixedPoint,
+    // the normal of the surface where the ray enters
+    entry_normal: Vec3,
+}
+
+impl Interval {
+    fn new(max_distance: FixedPoint) -> Interval {
+        Interval {
+            min: FixedPoint::zero(),
+            max: max_distance,
+            entry_normal: Vec3::zero(),
+        }
+    }
+
+    // keeps the distances t where a + b * t <= 0,
+    // the normal points out of the allowed half-space
+    fn clip(&mut self, a: FixedPoint, b: FixedPoint, normal: Vec3) {
+        if b.is_zero() {
+            if a.is_positive() {
+                self.max = self.min - FixedPoint::one();
+            }
+        } else {
+            let t = -a / b;
+            if b.is_positive() {
+                self.max = self.max.min(t);
+            } else if t > self.min {
+                self.min = t;
+                self.entry_normal = normal;
+            }
+        }
+    }
+
+    fn clip_box(&mut self, origin: Vec3, dir: Vec3, min: Vec3, max: Vec3) {
+        let zero = FixedPoint::zero();
+        let one = FixedPoint::one();
+        self.clip(origin.x - max.x, dir.x, Vec3::new(one, zero, zero));
+        self.clip(min.x - origin.x, -dir.x, Vec3::new(-one, zero, zero));
+        self.clip(origin.y - max.y, dir.y, Vec3::new(zero, one, zero));
+        self.clip(min.y - origin.y, -dir.y, Vec3::new(zero, -one, zero));
+        self.clip(origin.z - max.z, dir.z, Vec3::new(zero, zero, one));
+        self.clip(min.z - origin.z, -dir.z, Vec3::new(zero, zero, -one));
+    }
+
+    fn entry(&self) -> Option<RayHit> {
+        if self.min <= self.max {
+            Some(RayHit {
+                distance: self.min,
+                normal: self.entry_normal,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Slope {
+    PositiveX,
+    NegativeX,
+    PositiveY,
+    NegativeY,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Shape {
+    Box {
+        min: Vec3,
+        max: Vec3,
+    },
+    // a box rotated around the z-axis
+    OrientedBox {
+        center: Vec3,
+        half_extents: Vec3,
+        yaw: FPAngle,
+    },
+    // a ramp that rises from min.z to max.z in the slope direction,
+    // solid below its surface
+    SlopedPlane {
+        min: Vec3,
+        max: Vec3,
+        slope: Slope,
+    },
+}
+
+struct Contact {
+    push: Vec3,
+    // unit length
+    normal: Vec3,
+}
+
+impl Shape {
+    fn raycast(&self, ray: &Ray, max_distance: FixedPoint) -> Option<RayHit> {
+        let mut interval = Interval::new(max_distance);
+        match *self {
+            Shape::Box { min, max } => {
+                interval.clip_box(ray.origin, ray.dir, min, max);
+            },
+            Shape::OrientedBox { center, half_extents, yaw } => {
+                // transform the ray into the local space of the box
+                let s = yaw.sin();
+                let c = yaw.cos();
+                let to_local = |v: Vec3| Vec3::new(v.x * c + v.y * s, v.y * c - v.x * s, v.z);
+                let to_world = |v: Vec3| Vec3::new(v.x * c - v.y * s, v.x * s + v.y * c, v.z);
+                interval.clip_box(
+                    to_local(ray.origin - center),
+                    to_local(ray.dir),
+                    -half_extents,
+                    half_extents,
+                );
+                interval.entry_normal = to_world(interval.entry_normal);
+            },
+            Shape::SlopedPlane { min, max, slope } => {
+                interval.clip_box(ray.origin, ray.dir, min, max);
+                // below the surface z = min.z + gx * (x - ax) + gy * (y - ay)
+                let zero = FixedPoint::zero();
+                let (ax, ay, gx, gy) = match slope {
+                    Slope::PositiveX => (min.x, min.y, (max.z - min.z) / (max.x - min.x), zero),
+                    Slope::NegativeX => (max.x, min.y, (min.z - max.z) / (max.x - min.x), zero),
+                    Slope::PositiveY => (min.x, min.y, zero, (max.z - min.z) / (max.y - min.y)),
+                    Slope::NegativeY => (min.x, max.y, zero, (min.z - max.z) / (max.y - min.y)),
+                };
+                let normal = Vec3::new(-gx, -gy, FixedPoint::one());
+                interval.clip(
+                    ray.origin.z - min.z - gx * (ray.origin.x - ax) - gy * (ray.origin.y - ay),
+                    ray.dir.z - gx * ray.dir.x - gy * ray.dir.y,
+                    normal.scale_to(FixedPoint::one()),
+                );
+            },
+        }
+        interval.entry()
+    }
+
+    fn contact(&self, body: &Body) -> Option<Contact> {
+        match *self {
+            Shape::Box { min, max } => {
+                penetration(body.min(), body.max(), min, max)
+            },
+            Shape::OrientedBox { center, half_extents, yaw } => {
+                // transform the body into the local space of the box,
+                // the footprint of the body turns with the box
+                let s = yaw.sin();
+                let c = yaw.cos();
+                let dx = body.feet.x - center.x;
+                let dy = body.feet.y - center.y;
+                let local_body = Body {
+                    feet: Vec3::new(dx * c + dy * s, dy * c - dx * s, body.feet.z - center.z),
+                    ..*body
+                };
+                let local_contact = penetration(
+                    local_body.min(),
+                    local_body.max(),
+                    -half_extents,
+                    half_extents,
+                );
+                let rotate = |v: Vec3| Vec3::new(v.x * c - v.y * s, v.x * s + v.y * c, v.z);
+                local_contact.map(|local_contact| Contact {
+                    push: rotate(local_contact.push),
+                    normal: rotate(local_contact.normal),
+                })
+            },
+            Shape::SlopedPlane { min, max, slope } => {
+                // TODO move to consts
+                let max_step = FixedPoint::fraction(1, 4);
+
+                // the surface height below the body, or at the closest point of the footprint
+                let x = body.feet.x.max(min.x).min(max.x);
+                let y = body.feet.y.max(min.y).min(max.y);
+                let ratio = match slope {
+                    Slope::PositiveX => (x - min.x) / (max.x - min.x),
+                    Slope::NegativeX => (max.x - x) / (max.x - min.x),
+                    Slope::PositiveY => (y - min.y) / (max.y - min.y),
+                    Slope::NegativeY => (max.y - y) / (max.y - min.y),
+                };
+                let surface = min.z.mix(max.z, ratio);
+                let top = Vec3::new(max.x, max.y, surface);
+                let wall_contact = penetration(body.min(), body.max(), min, top)?;
+                let inside = x == body.feet.x && y == body.feet.y;
+                let depth = surface - body.feet.z;
+                if inside && depth <= max_step {
+                    // walk up the ramp
+                    let up = Vec3::new(FixedPoint::zero(), FixedPoint::zero(), FixedPoint::one());
+                    Some(Contact {
+                        push: up * depth,
+                        normal: up,
+                    })
+                } else {
+                    // treat it like a box
+                    Some(wall_contact)
+                }
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SpawnPoint {
+    // position of the feet
+    pub pos: Vec3,
+    pub yaw: FPAngle,
+}
+
+// Static collision geometry, spawn points, pickup placements and triggers,
+// loaded once and shared by all copies of a world.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Level {
+    // an infinite floor plane
+    floor: Option<FixedPoint>,
+    shapes: Vec<Shape>,
+    spawn_points: Vec<SpawnPoint>,
+    pickups: Vec<PickupSpawn>,
+    // evaluated in order, a character is only affected by the first one it touches
+    triggers: Vec<Trigger>,
+}
+
+impl Level {
+    pub fn new(floor: Option<FixedPoint>, shapes: Vec<Shape>, spawn_points: Vec<SpawnPoint>,
+               pickups: Vec<PickupSpawn>, triggers: Vec<Trigger>) -> Level {
+        Level {
+            floor,
+            shapes,
+            spawn_points,
+            pickups,
+            triggers,
+        }
+    }
+
+    pub fn floor(&self) -> Option<FixedPoint> {
+        self.floor
+    }
+
+    pub fn shapes(&self) -> &[Shape] {
+        &self.shapes
+    }
+
+    pub fn spawn_points(&self) -> &[SpawnPoint] {
+        &self.spawn_points
+    }
+
+    pub fn pickups(&self) -> &[PickupSpawn] {
+        &self.pickups
+    }
+
+    pub fn triggers(&self) -> &[Trigger] {
+        &self.triggers
+    }
+
+    // Cycles through the spawn points, falls back to the origin if there are none.
+    pub fn spawn_point(&self, index: u64) -> SpawnPoint {
+        if self.spawn_points.is_empty() {
+            SpawnPoint {
+                pos: Vec3::zero(),
+                yaw: FPAngle::zero(),
+            }
+        } else {
+            self.spawn_points[(index % self.spawn_points.len() as u64) as usize]
+        }
+    }
+
+    // Returns the first solid thing the ray hits.
+    pub fn raycast(&self, ray: &Ray, max_distance: FixedPoint) -> Option<RayHit> {
+        let mut nearest: Option<RayHit> = None;
+        if let Some(floor) = self.floor {
+            let mut interval = Interval::new(max_distance);
+            let up = Vec3::new(FixedPoint::zero(), FixedPoint::zero(), FixedPoint::one());
+            interval.clip(ray.origin.z - floor, ray.dir.z, up);
+            nearest = interval.entry();
+        }
+        for shape in self.shapes.iter() {
+            let limit = nearest.map(|hit| hit.distance).unwrap_or(max_distance);
+            if let Some(hit) = shape.raycast(ray, limit) {
+                nearest = Some(hit);
+            }
+        }
+        nearest
+    }
+
+    // Moves the body out of all shapes and cancels the velocity into them.
+    // Shapes are resolved in order, so the result is the same on all machines.
+    // Returns whether the body stands on something.
+    pub fn collide(&self, body: &mut Body, vel: &mut Vec3) -> bool {
+        let mut grounded = false;
+        for _ in 0..COLLISION_ITERATIONS {
+            let mut collided = false;
+            if let Some(floor) = self.floor {
+                if body.feet.z <= floor {
+                    body.feet.z = floor;
+                    if vel.z.is_negative() {
+                        vel.z = FixedPoint::zero();
+                    }
+                    grounded = true;
+                }
+            }
+            for shape in self.shapes.iter() {
+                if let Some(contact) = shape.contact(body) {
+                    if !contact.push.is_zero() {
+                        body.feet += contact.push;
+                        collided = true;
+                    }
+                    let normal_vel = vel.dot(contact.normal);
+                    if normal_vel.is_negative() {
+                        *vel -= contact.normal * normal_vel;
+                    }
+                    if contact.normal.z.is_positive() {
+                        grounded = true;
+                    }
+                }
+            }
+            if !collided {
+                break;
+            }
+        }
+        grounded
+    }
+}
+
+impl Default for Level {
+    fn default() -> Level {
+        Level {
+            floor: Some(FixedPoint::zero()),
+            shapes: Vec::new(),
+            spawn_points: Vec::new(),
+            pickups: Vec::new(),
+            triggers: Vec::new(),
+        }
+    }
+}
+
+// Returns the shortest axis-aligned push out of the box, if the body overlaps it.
+// Touching counts as overlapping, so bodies resting on top of a box stay grounded.
+fn penetration(body_min: Vec3, body_max: Vec3, min: Vec3, max: Vec3) -> Option<Contact> {
+    if body_max.x <= min.x || body_min.x >= max.x
+        || body_max.y <= min.y || body_min.y >= max.y
+        || body_max.z < min.z || body_min.z > max.z {
+        return None;
+    }
+    let zero = FixedPoint::zero();
+    let one = FixedPoint::one();
+    // in order of preference
+    let candidates = [
+        (max.z - body_min.z, Vec3::new(zero, zero, one)),
+        (body_max.z - min.z, Vec3::new(zero, zero, -one)),
+        (max.x - body_min.x, Vec3::new(one, zero, zero)),
+        (body_max.x - min.x, Vec3::new(-one, zero, zero)),
+        (max.y - body_min.y, Vec3::new(zero, one, zero)),
+        (body_max.y - min.y, Vec3::new(zero, -one, zero)),
+    ];
+    let mut best = candidates[0];
+    for &candidate in candidates[1..].iter() {
+        if candidate.0 < best.0 {
+            best = candidate;
+        }
+    }
+    let (depth, normal) = best;
+    Some(Contact {
+        push: normal * depth,
+        normal,
+    })
+}
\ No newline at end of file
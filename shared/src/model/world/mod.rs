@@ -1,54 +1,487 @@
 pub mod character;
+pub mod entity;
+pub mod level;
+pub mod projectile;
+pub mod pickup;
+pub mod trigger;
 
-use std::collections::HashMap;
+use std::sync::Arc;
+
+use consts::RESPAWN_DELAY_TICKS;
+use consts::HITSCAN_RANGE;
+use consts::HITSCAN_DAMAGE;
+use math::FixedPoint;
+use math::Vec3;
+use util::Fnv1a;
 
 use self::character::Character;
 use self::character::CharacterInput;
+use self::entity::Entities;
+use self::entity::Entity;
+use self::entity::EntityId;
+use self::level::Level;
+use self::level::Ray;
+use self::level::SpawnPoint;
+use self::projectile::Projectile;
+use self::pickup::Pickup;
+use self::pickup::PickupKind;
+use self::pickup::PickupSpawn;
+use self::trigger::TriggerAction;
+use model::movement_params::MovementParams;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Hit {
+    pub character_id: EntityId,
+    pub distance: FixedPoint,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Kill {
+    pub killer: EntityId,
+    pub victim: EntityId,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct PickupEvent {
+    // the index of the level's pickup
+    pub pickup_index: usize,
+    pub character_id: EntityId,
+    pub kind: PickupKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DamageResult {
+    Damaged,
+    Killed,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct World {
-    characters: HashMap<u64, Character>,
-    next_character_id: u64,
+    // not sent in snapshots, every side loads the level itself
+    #[serde(skip)]
+    level: Arc<Level>,
+    // not sent in snapshots either, clients get them on connect
+    #[serde(skip)]
+    movement_params: MovementParams,
+    entities: Entities,
+    // by index of the level's pickups
+    pickups: Vec<Pickup>,
+    // spawn points are used round-robin
+    next_spawn_point: u64,
+    // kills that haven't been taken by the model yet
+    kills: Vec<Kill>,
+    // pickups taken in the last tick
+    pickup_events: Vec<PickupEvent>,
 }
 
 impl World {
-    pub fn new() -> Self {
+    pub fn new(level: Arc<Level>) -> Self {
+        let pickups = vec![Pickup::default(); level.pickups().len()];
         World {
-            characters: HashMap::new(),
-            next_character_id: 0,
+            level,
+            movement_params: MovementParams::default(),
+            entities: Entities::default(),
+            pickups,
+            next_spawn_point: 0,
+            kills: Vec::new(),
+            pickup_events: Vec::new(),
         }
     }
 
-    pub fn set_character_input(&mut self, character_id: u64, input: CharacterInput) {
-        if let Some(c) = self.characters.get_mut(&character_id) {
+    pub fn level(&self) -> &Arc<Level> {
+        &self.level
+    }
+
+    pub fn set_level(&mut self, level: Arc<Level>) {
+        self.level = level;
+    }
+
+    pub fn movement_params(&self) -> &MovementParams {
+        &self.movement_params
+    }
+
+    pub fn set_movement_params(&mut self, movement_params: MovementParams) {
+        self.movement_params = movement_params;
+    }
+
+    pub fn entities(&self) -> &Entities {
+        &self.entities
+    }
+
+    // The same on all machines with the same state, the level isn't included.
+    pub fn checksum(&self) -> u64 {
+        let mut hasher = Fnv1a::new();
+        hasher.add(&self.movement_params);
+        hasher.add(&self.entities);
+        hasher.add(&self.pickups);
+        hasher.add(&self.next_spawn_point);
+        hasher.add(&self.kills);
+        hasher.add(&self.pickup_events);
+        hasher.finish()
+    }
+
+    // Ordered by id, helps finding out where simulations diverged.
+    pub fn entity_checksums(&self) -> Vec<(EntityId, u64)> {
+        self.entities.iter().map(|(id, entity)| {
+            let mut hasher = Fnv1a::new();
+            hasher.add(entity);
+            (id, hasher.finish())
+        }).collect()
+    }
+
+    pub fn set_character_input(&mut self, character_id: EntityId, input: CharacterInput) {
+        if let Some(c) = self.entities.character_mut(character_id) {
             c.set_input(input);
         }
     }
 
-    pub fn spawn_character(&mut self) -> u64 {
-        let character = Character::new();
-        self.characters.insert(self.next_character_id, character);
-        self.next_character_id += 1;
-        self.next_character_id - 1
+    pub fn spawn_character(&mut self) -> EntityId {
+        let spawn_point = self.take_spawn_point();
+        let character = Character::new(spawn_point, &self.movement_params);
+        self.entities.insert(Entity::Character(character))
     }
 
-    pub fn remove_character(&mut self, character_id: u64) {
-        if let None = self.characters.remove(&character_id) {
+    pub fn remove_character(&mut self, character_id: EntityId) {
+        if self.entities.character(character_id).is_some() {
+            self.entities.remove(character_id);
+        } else {
             println!("WARNING: Tried to remove non-existing character with id {}!", character_id);
         }
     }
 
-    pub fn character(&self, character_id: u64) -> Option<&Character> {
-        self.characters.get(&character_id)
+    // Moves a character to the next spawn point, alive and with full health.
+    pub fn respawn_character(&mut self, character_id: EntityId) {
+        if let Some(input) = self.entities.character(character_id).map(|c| c.input()) {
+            let spawn_point = self.take_spawn_point();
+            let mut character = Character::new(spawn_point, &self.movement_params);
+            character.keep_input(input);
+            *self.entities.character_mut(character_id).unwrap() = character;
+        }
     }
 
-    pub fn characters(&self) -> &HashMap<u64, Character> {
-        &self.characters
+    // The character waits for the respawn delay without counting as killed.
+    pub fn delay_respawn(&mut self, character_id: EntityId) {
+        if let Some(character) = self.entities.character_mut(character_id) {
+            character.kill(RESPAWN_DELAY_TICKS);
+        }
+    }
+
+    // Returns None if there is no living character with that id.
+    pub fn damage_character(&mut self, character_id: EntityId, damage: u64,
+                            attacker_id: EntityId) -> Option<DamageResult> {
+        let character = self.entities.character_mut(character_id)?;
+        if !character.alive() {
+            return None;
+        }
+        if character.damage(damage, RESPAWN_DELAY_TICKS) {
+            self.kills.push(Kill {
+                killer: attacker_id,
+                victim: character_id,
+            });
+            Some(DamageResult::Killed)
+        } else {
+            Some(DamageResult::Damaged)
+        }
+    }
+
+    pub fn take_kills(&mut self) -> Vec<Kill> {
+        self.kills.split_off(0)
+    }
+
+    pub fn character(&self, character_id: EntityId) -> Option<&Character> {
+        self.entities.character(character_id)
+    }
+
+    pub fn characters<'a>(&'a self) -> impl Iterator<Item = (EntityId, &'a Character)> + 'a {
+        self.entities.characters()
+    }
+
+    pub fn projectiles<'a>(&'a self) -> impl Iterator<Item = (EntityId, &'a Projectile)> + 'a {
+        self.entities.projectiles()
+    }
+
+    // With the index, the level's placement and the state.
+    pub fn pickups<'a>(&'a self)
+        -> impl Iterator<Item = (usize, &'a PickupSpawn, &'a Pickup)> + 'a
+    {
+        self.level.pickups().iter().zip(self.pickups.iter())
+            .enumerate()
+            .map(|(index, (spawn, pickup))| (index, spawn, pickup))
+    }
+
+    pub fn pickup_events(&self) -> &[PickupEvent] {
+        &self.pickup_events
+    }
+
+    // The shots fired in the last tick.
+    pub fn shots(&self) -> Vec<(EntityId, Ray)> {
+        self.characters()
+            .filter_map(|(id, c)| c.shot().map(|ray| (id, ray)))
+            .collect()
+    }
+
+    // Returns the closest character hit by the ray, if the level doesn't block it first.
+    pub fn hitscan(&self, ray: &Ray, max_distance: FixedPoint,
+                   shooter_id: EntityId) -> Option<Hit> {
+        let max_distance = self.level.raycast(ray, max_distance)
+            .map(|hit| hit.distance)
+            .unwrap_or(max_distance);
+        first_character_hit(&self.entities, ray, max_distance, shooter_id)
+    }
+
+    // Damages the characters hit by the shots of the last tick and returns the hits by shooter.
+    // view_world gives the world the shooter saw, e.g. rewound for lag compensation,
+    // None checks against this world.
+    pub fn apply_shots<'a, F>(&mut self, view_world: F) -> Vec<(EntityId, Hit)>
+        where F: Fn(EntityId) -> Option<&'a World>
+    {
+        let mut hits = Vec::new();
+        for (character_id, ray) in self.shots() {
+            let range = FixedPoint::new(HITSCAN_RANGE);
+            let hit = match view_world(character_id) {
+                Some(world) => world.hitscan(&ray, range, character_id),
+                None => self.hitscan(&ray, range, character_id),
+            };
+            if let Some(hit) = hit {
+                hits.push((character_id, hit));
+            }
+        }
+        // apply the damage after checking all shots, so everyone who fired this tick gets a shot
+        for &(character_id, hit) in hits.iter() {
+            self.damage_character(hit.character_id, HITSCAN_DAMAGE, character_id);
+        }
+        hits
     }
 
     pub fn do_tick(&mut self) {
-        for (_, mut c) in self.characters.iter_mut() {
-            c.do_tick();
+        self.pickup_events.clear();
+        // entities are always handled in the same order,
+        // because client prediction has to produce the same result as the server
+        let ids: Vec<EntityId> = self.characters().map(|(id, _)| id).collect();
+        let mut living_ids = Vec::with_capacity(ids.len());
+        for id in ids {
+            let respawn = {
+                let character = self.entities.character_mut(id).unwrap();
+                if character.alive() {
+                    character.do_tick(&self.level, &self.movement_params);
+                    living_ids.push(id);
+                    false
+                } else {
+                    character.tick_respawn_delay()
+                }
+            };
+            if respawn {
+                self.respawn_character(id);
+            }
+        }
+        self.collide_characters(&living_ids);
+        self.apply_triggers(&living_ids);
+        self.tick_pickups(&living_ids);
+        self.tick_projectiles();
+        for id in living_ids {
+            let launched = self.entities.character(id).unwrap().launched_projectile();
+            if let Some((kind, launch)) = launched {
+                self.entities.insert(Entity::Projectile(Projectile::new(kind, id, launch)));
+            }
+        }
+    }
+
+    // Instead of a tick, so the shots and pickups of the last one don't repeat.
+    pub fn stand_still(&mut self) {
+        self.pickup_events.clear();
+        let ids: Vec<EntityId> = self.characters().map(|(id, _)| id).collect();
+        for id in ids {
+            self.entities.character_mut(id).unwrap().clear_shot();
         }
     }
+
+    // Kill zones count as suicides.
+    fn apply_triggers(&mut self, living_ids: &[EntityId]) {
+        for &id in living_ids {
+            let body = self.entities.character(id).unwrap().body();
+            let action = match self.level.triggers().iter().find(|trigger| trigger.touches(&body)) {
+                Some(trigger) => trigger.action,
+                None => continue,
+            };
+            let character = self.entities.character_mut(id).unwrap();
+            match action {
+                TriggerAction::Teleport { destination, yaw, keep_velocity } => {
+                    character.teleport(destination, yaw, keep_velocity);
+                },
+                TriggerAction::JumpPad { velocity } => character.launch(velocity),
+                TriggerAction::Kill => {
+                    if character.kill(RESPAWN_DELAY_TICKS) {
+                        self.kills.push(Kill {
+                            killer: id,
+                            victim: id,
+                        });
+                    }
+                },
+            }
+        }
+    }
+
+    // A pickup goes to the first character touching it that has a use for it.
+    fn tick_pickups(&mut self, living_ids: &[EntityId]) {
+        let pickups = self.level.pickups().iter().zip(self.pickups.iter_mut()).enumerate();
+        for (index, (spawn, pickup)) in pickups {
+            pickup.do_tick();
+            if pickup.available() {
+                for &character_id in living_ids {
+                    let character = self.entities.character_mut(character_id).unwrap();
+                    if spawn.touches(&character.body()) && character.pick_up(spawn.kind) {
+                        pickup.take(spawn.kind);
+                        self.pickup_events.push(PickupEvent {
+                            pickup_index: index,
+                            character_id,
+                            kind: spawn.kind,
+                        });
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    fn tick_projectiles(&mut self) {
+        let ids: Vec<EntityId> = self.projectiles().map(|(id, _)| id).collect();
+        for id in ids {
+            // the projectile looks at the other entities while it moves
+            let mut projectile = self.entities.projectile(id).unwrap().clone();
+            if projectile.do_tick(&self.level, &self.entities) {
+                self.entities.remove(id);
+                self.explode(&projectile);
+            } else {
+                *self.entities.projectile_mut(id).unwrap() = projectile;
+            }
+        }
+    }
+
+    // Damages all characters within the splash radius, including the owner.
+    fn explode(&mut self, projectile: &Projectile) {
+        // explosions happen on the level's surface, which shouldn't block the splash
+        let surface_offset = FixedPoint::fraction(1, 100);
+
+        let params = projectile.kind().params();
+        let center = projectile.pos();
+        let ids: Vec<EntityId> = self.characters().map(|(id, _)| id).collect();
+        for id in ids {
+            let body = self.entities.character(id).unwrap().body();
+            let offset = body.closest_point(center) - center;
+            let distance = offset.length2().sqrt();
+            if distance >= params.splash_radius {
+                continue;
+            }
+            // no splash damage through walls
+            let ray_length = distance - surface_offset - surface_offset;
+            if ray_length.is_positive() {
+                let dir = offset / distance;
+                let ray = Ray {
+                    origin: center + dir * surface_offset,
+                    dir,
+                };
+                if self.level.raycast(&ray, ray_length).is_some() {
+                    continue;
+                }
+            }
+            let falloff = (params.splash_radius - distance) / params.splash_radius;
+            let damage = (FixedPoint::new(params.splash_damage as i64) * falloff).floor();
+            if damage > 0 {
+                self.damage_character(id, damage as u64, projectile.owner());
+            }
+        }
+    }
+
+    fn take_spawn_point(&mut self) -> SpawnPoint {
+        let spawn_point = self.level.spawn_point(self.next_spawn_point);
+        self.next_spawn_point += 1;
+        spawn_point
+    }
+
+    // Pushes overlapping characters apart, treating them as vertical cylinders.
+    fn collide_characters(&mut self, ordered_ids: &[EntityId]) {
+        let zero = FixedPoint::zero();
+        for (i, &id_a) in ordered_ids.iter().enumerate() {
+            for &id_b in ordered_ids[(i + 1)..].iter() {
+                let body_a = self.entities.character(id_a).unwrap().body();
+                let body_b = self.entities.character(id_b).unwrap().body();
+                if body_a.feet.z >= body_b.feet.z + body_b.height
+                    || body_b.feet.z >= body_a.feet.z + body_a.height {
+                    continue;
+                }
+                let dx = body_b.feet.x - body_a.feet.x;
+                let dy = body_b.feet.y - body_a.feet.y;
+                let min_dist = body_a.radius + body_b.radius;
+                let dist2 = dx * dx + dy * dy;
+                if dist2 >= min_dist * min_dist {
+                    continue;
+                }
+                let dist = dist2.sqrt();
+                let dir = if dist.is_zero() {
+                    // on top of each other, the first one goes to negative x
+                    Vec3::new(FixedPoint::one(), zero, zero)
+                } else {
+                    Vec3::new(dx / dist, dy / dist, zero)
+                };
+                let push = dir * ((min_dist - dist) / 2);
+                self.entities.character_mut(id_a).unwrap().push(-push, -dir, &self.level);
+                self.entities.character_mut(id_b).unwrap().push(push, dir, &self.level);
+            }
+        }
+    }
+}
+
+// Returns the closest living character hit by the ray, ignoring one character.
+fn first_character_hit(entities: &Entities, ray: &Ray, max_distance: FixedPoint,
+                       ignored_id: EntityId) -> Option<Hit> {
+    let mut nearest: Option<Hit> = None;
+    for (id, character) in entities.characters() {
+        if id == ignored_id || !character.alive() {
+            continue;
+        }
+        let limit = nearest.map(|hit| hit.distance).unwrap_or(max_distance);
+        if let Some(ray_hit) = character.body().raycast(ray, limit) {
+            if nearest.map(|hit| ray_hit.distance < hit.distance).unwrap_or(true) {
+                nearest = Some(Hit {
+                    character_id: id,
+                    distance: ray_hit.distance,
+                });
+            }
+        }
+    }
+    nearest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use math::FPAngle;
+    use self::level::Shape;
+    use self::projectile::ProjectileKind;
+
+    fn point(x: i64, y: i64, z: i64) -> Vec3 {
+        Vec3::new(FixedPoint::new(x), FixedPoint::new(y), FixedPoint::new(z))
+    }
+
+    #[test]
+    fn walls_block_splash_damage() {
+        let wall = Shape::Box { min: point(1, -5, 0), max: point(2, 5, 3) };
+        let spawn_points = vec![
+            SpawnPoint { pos: point(0, 0, 0), yaw: FPAngle::zero() },
+            SpawnPoint { pos: point(3, 0, 0), yaw: FPAngle::zero() },
+        ];
+        let level = Level::new(Some(FixedPoint::zero()), vec![wall], spawn_points,
+                               Vec::new(), Vec::new());
+        let mut world = World::new(Arc::new(level));
+        let front_id = world.spawn_character();
+        let behind_id = world.spawn_character();
+        let full_health = world.character(behind_id).unwrap().health();
+        // a rocket that hit the front of the wall
+        let launch = Ray { origin: point(1, 0, 1), dir: point(1, 0, 0) };
+        let rocket = Projectile::new(ProjectileKind::Rocket, front_id, launch);
+        world.explode(&rocket);
+        assert!(world.character(front_id).unwrap().health() < full_health);
+        assert_eq!(world.character(behind_id).unwrap().health(), full_health);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,90 @@
+use consts::TICK_SPEED;
+use consts::HEALTH_PICKUP_AMOUNT;
+use consts::ARMOR_PICKUP_AMOUNT;
+use consts::HEALTH_PICKUP_RESPAWN_DELAY;
+use consts::ARMOR_PICKUP_RESPAWN_DELAY;
+use consts::AMMO_PICKUP_RESPAWN_DELAY;
+use consts::WEAPON_PICKUP_RESPAWN_DELAY;
+use math::FixedPoint;
+use math::Vec3;
+use model::world::level::Body;
+use model::world::character::Weapon;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PickupKind {
+    Health,
+    Armor,
+    Ammo(Weapon),
+    // also gives the ammo of an ammo pickup
+    Weapon(Weapon),
+}
+
+pub struct PickupParams {
+    // health, armor or ammo
+    pub amount: u64,
+    // ticks until the pickup is back after it was taken
+    pub respawn_delay: u64,
+}
+
+impl PickupKind {
+    pub fn params(&self) -> PickupParams {
+        match *self {
+            PickupKind::Health => PickupParams {
+                amount: HEALTH_PICKUP_AMOUNT,
+                respawn_delay: TICK_SPEED.ticks(HEALTH_PICKUP_RESPAWN_DELAY),
+            },
+            PickupKind::Armor => PickupParams {
+                amount: ARMOR_PICKUP_AMOUNT,
+                respawn_delay: TICK_SPEED.ticks(ARMOR_PICKUP_RESPAWN_DELAY),
+            },
+            PickupKind::Ammo(weapon) => PickupParams {
+                amount: weapon.pickup_ammo(),
+                respawn_delay: TICK_SPEED.ticks(AMMO_PICKUP_RESPAWN_DELAY),
+            },
+            PickupKind::Weapon(weapon) => PickupParams {
+                amount: weapon.pickup_ammo(),
+                respawn_delay: TICK_SPEED.ticks(WEAPON_PICKUP_RESPAWN_DELAY),
+            },
+        }
+    }
+}
+
+// Where the map places a pickup.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct PickupSpawn {
+    pub kind: PickupKind,
+    // the center of the pickup
+    pub pos: Vec3,
+}
+
+impl PickupSpawn {
+    pub fn touches(&self, body: &Body) -> bool {
+        let radius = FixedPoint::fraction(1, 2);
+        (body.closest_point(self.pos) - self.pos).length2() < radius * radius
+    }
+}
+
+// What changes about a pickup, the rest is in the level,
+// so snapshots stay small.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Pickup {
+    // the pickup can be taken when this is zero
+    respawn_delay: u64,
+}
+
+impl Pickup {
+    pub fn available(&self) -> bool {
+        self.respawn_delay == 0
+    }
+
+    // Counts down the respawn delay of a taken pickup.
+    pub fn do_tick(&mut self) {
+        if self.respawn_delay > 0 {
+            self.respawn_delay -= 1;
+        }
+    }
+
+    pub fn take(&mut self, kind: PickupKind) {
+        self.respawn_delay = kind.params().respawn_delay;
+    }
+}
\ No newline at end of file
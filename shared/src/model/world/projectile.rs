@@ -0,0 +1,134 @@
+use consts::TICK_SPEED;
+use consts::ROCKET_SPEED;
+use consts::GRENADE_SPEED;
+use consts::GRENADE_GRAVITY;
+use consts::GRENADE_BOUNCINESS;
+use consts::ROCKET_FUSE;
+use consts::GRENADE_FUSE;
+use consts::ROCKET_SPLASH_RADIUS;
+use consts::GRENADE_SPLASH_RADIUS;
+use consts::ROCKET_SPLASH_DAMAGE;
+use consts::GRENADE_SPLASH_DAMAGE;
+use math::FixedPoint;
+use math::Vec3;
+use model::world::level::Level;
+use model::world::level::Ray;
+use model::world::entity::Entities;
+use model::world::entity::EntityId;
+use model::world::first_character_hit;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProjectileKind {
+    Rocket,
+    Grenade,
+}
+
+pub struct ProjectileParams {
+    // per tick
+    pub speed: FixedPoint,
+    pub gravity: FixedPoint,
+    // the share of the velocity kept when bouncing off the level,
+    // projectiles without one explode on contact
+    pub bounciness: Option<FixedPoint>,
+    // ticks until the projectile explodes by itself
+    pub fuse: u64,
+    pub splash_radius: FixedPoint,
+    // at the center of the explosion, falls off linearly to the splash radius
+    pub splash_damage: u64,
+}
+
+impl ProjectileKind {
+    pub fn params(&self) -> ProjectileParams {
+        match *self {
+            ProjectileKind::Rocket => ProjectileParams {
+                speed: TICK_SPEED.per_tick(ROCKET_SPEED),
+                gravity: FixedPoint::zero(),
+                bounciness: None,
+                fuse: TICK_SPEED.ticks(ROCKET_FUSE),
+                splash_radius: FixedPoint::new(ROCKET_SPLASH_RADIUS),
+                splash_damage: ROCKET_SPLASH_DAMAGE,
+            },
+            ProjectileKind::Grenade => ProjectileParams {
+                speed: TICK_SPEED.per_tick(GRENADE_SPEED),
+                gravity: TICK_SPEED.per_tick_squared(GRENADE_GRAVITY),
+                bounciness: Some(FixedPoint::from_float(GRENADE_BOUNCINESS)),
+                fuse: TICK_SPEED.ticks(GRENADE_FUSE),
+                splash_radius: FixedPoint::new(GRENADE_SPLASH_RADIUS),
+                splash_damage: GRENADE_SPLASH_DAMAGE,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Projectile {
+    kind: ProjectileKind,
+    // the character that fired it
+    owner: EntityId,
+    pos: Vec3,
+    vel: Vec3,
+    fuse: u64,
+}
+
+impl Projectile {
+    pub fn new(kind: ProjectileKind, owner: EntityId, launch: Ray) -> Projectile {
+        let params = kind.params();
+        Projectile {
+            kind,
+            owner,
+            pos: launch.origin,
+            vel: launch.dir * params.speed,
+            fuse: params.fuse,
+        }
+    }
+
+    pub fn kind(&self) -> ProjectileKind {
+        self.kind
+    }
+
+    pub fn owner(&self) -> EntityId {
+        self.owner
+    }
+
+    pub fn pos(&self) -> Vec3 {
+        self.pos
+    }
+
+    // Returns whether the projectile exploded.
+    pub fn do_tick(&mut self, level: &Level, entities: &Entities) -> bool {
+        // keeps bounced projectiles from starting inside the level
+        let bounce_offset = FixedPoint::fraction(1, 100);
+
+        let params = self.kind.params();
+        self.vel.z -= params.gravity;
+        let length = self.vel.length2().sqrt();
+        if !length.is_zero() {
+            let ray = Ray {
+                origin: self.pos,
+                dir: self.vel / length,
+            };
+            let level_hit = level.raycast(&ray, length);
+            let limit = level_hit.map(|hit| hit.distance).unwrap_or(length);
+            if let Some(hit) = first_character_hit(entities, &ray, limit, self.owner) {
+                self.pos += ray.dir * hit.distance;
+                return true;
+            }
+            match (level_hit, params.bounciness) {
+                (Some(hit), Some(bounciness)) if !hit.normal.is_zero() => {
+                    self.pos += ray.dir * hit.distance + hit.normal * bounce_offset;
+                    let normal_vel = self.vel.dot(hit.normal);
+                    if normal_vel.is_negative() {
+                        self.vel -= hit.normal * (normal_vel * (FixedPoint::one() + bounciness));
+                    }
+                },
+                (Some(hit), _) => {
+                    self.pos += ray.dir * hit.distance;
+                    return true;
+                },
+                (None, _) => self.pos += self.vel,
+            }
+        }
+        self.fuse -= 1;
+        self.fuse == 0
+    }
+}
\ No newline at end of file
@@ -0,0 +1,39 @@
+use math::FPAngle;
+use math::Vec3;
+use model::world::level::Body;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum TriggerAction {
+    Teleport {
+        // where the feet end up
+        destination: Vec3,
+        // None keeps the view direction
+        yaw: Option<FPAngle>,
+        keep_velocity: bool,
+    },
+    // replaces the velocity, per tick
+    JumpPad {
+        velocity: Vec3,
+    },
+    Kill,
+}
+
+// An axis-aligned box that does something to the living characters touching it.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Trigger {
+    pub min: Vec3,
+    pub max: Vec3,
+    pub action: TriggerAction,
+}
+
+impl Trigger {
+    // Just touching the trigger from the outside doesn't count,
+    // so characters standing on top of a trigger box aren't affected.
+    pub fn touches(&self, body: &Body) -> bool {
+        let body_min = body.min();
+        let body_max = body.max();
+        body_max.x > self.min.x && body_min.x < self.max.x
+            && body_max.y > self.min.y && body_min.y < self.max.y
+            && body_max.z > self.min.z && body_min.z < self.max.z
+    }
+}
\ No newline at end of file
@@ -0,0 +1,102 @@
+use std::io;
+use std::time::Instant;
+
+use mio::Poll;
+use mio::Events;
+use mio::Evented;
+use mio::Token;
+use mio::Ready;
+use mio::PollOpt;
+use mio::Registration;
+use mio::SetReadiness;
+
+const SOCKET_TOKEN: Token = Token(0);
+const WAKEUP_TOKEN: Token = Token(1);
+const EVENTS_CAPACITY: usize = 16;
+
+pub enum WaitResult {
+    Readable,
+    WokenUp,
+    TimedOut,
+}
+
+// Can be sent to other threads to interrupt a waiting EventPoll.
+#[derive(Clone)]
+pub struct Waker {
+    set_readiness: SetReadiness,
+}
+
+impl Waker {
+    pub fn wake(&self) {
+        if let Err(e) = self.set_readiness.set_readiness(Ready::readable()) {
+            println!("ERROR: Could not wake up event poll: {:?}", e);
+        }
+    }
+}
+
+// Waits on a socket, a deadline and a wakeup registration at the same time.
+pub struct EventPoll {
+    poll: Poll,
+    events: Events,
+    // the registration must live as long as the poll
+    _registration: Registration,
+    waker: Waker,
+}
+
+impl EventPoll {
+    pub fn new() -> io::Result<EventPoll> {
+        let poll = Poll::new()?;
+        let (registration, set_readiness) = Registration::new2();
+        poll.register(&registration, WAKEUP_TOKEN, Ready::readable(), PollOpt::edge())?;
+        Ok(EventPoll {
+            poll,
+            events: Events::with_capacity(EVENTS_CAPACITY),
+            _registration: registration,
+            waker: Waker { set_readiness },
+        })
+    }
+
+    // The socket is registered level-triggered,
+    // so it doesn't have to be drained completely before waiting again.
+    pub fn register_socket<E: Evented>(&self, socket: &E) -> io::Result<()> {
+        self.poll.register(socket, SOCKET_TOKEN, Ready::readable(), PollOpt::level())
+    }
+
+    pub fn waker(&self) -> Waker {
+        self.waker.clone()
+    }
+
+    // Waits without a deadline if until is None.
+    pub fn wait(&mut self, until: Option<Instant>) -> io::Result<WaitResult> {
+        loop {
+            let timeout = match until {
+                Some(until) => {
+                    let now = Instant::now();
+                    if until <= now {
+                        return Ok(WaitResult::TimedOut);
+                    }
+                    Some(until - now)
+                },
+                None => None,
+            };
+            self.poll.poll(&mut self.events, timeout)?;
+            let mut readable = false;
+            let mut woken_up = false;
+            for event in self.events.iter() {
+                match event.token() {
+                    SOCKET_TOKEN => readable = true,
+                    WAKEUP_TOKEN => woken_up = true,
+                    _ => unreachable!(),
+                }
+            }
+            if woken_up {
+                self.waker.set_readiness.set_readiness(Ready::empty())?;
+                return Ok(WaitResult::WokenUp);
+            }
+            if readable {
+                return Ok(WaitResult::Readable);
+            }
+            // spurious wakeup, wait again
+        }
+    }
+}
\ No newline at end of file
@@ -1,4 +1,7 @@
 pub mod socket;
+pub mod event_poll;
+pub mod rate_limit;
+pub mod stream_socket;
 
 use std::io::Cursor;
 use std::cmp::Ordering;
@@ -9,11 +12,21 @@ use serde::Serialize;
 use serde::de::DeserializeOwned;
 
 use tick_time::TickInstant;
+use chat::ChatMessage;
 use model::Model;
+use model::movement_params::MovementParams;
 use model::world::character::CharacterInput;
+use model::world::entity::EntityId;
 
 pub const MAX_MESSAGE_LENGTH: usize = 1024;
 
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Transport {
+    Udp,
+    // fallback for networks that block UDP
+    Tcp,
+}
+
 pub trait Packable: Sized {
     fn unpack(buf: &[u8]) -> bincode::Result<Self>;
     fn pack(&self, buf: &mut [u8]) -> bincode::Result<usize>;
@@ -102,17 +115,30 @@ impl From<UnreliableServerMessage> for ServerMessage {
     }
 }
 
+// Lets clients check that they simulate a tick exactly like the server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TickChecksums {
+    // the model right before the tick, after the inputs were set
+    pub before_tick: u64,
+    // the model right after the tick, before the server changed it any further
+    pub after_tick: u64,
+    // only every ENTITY_CHECKSUM_INTERVAL ticks, they are big
+    pub entities: Option<Vec<(EntityId, u64)>>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Snapshot {
     tick: u64,
-    model: Model
+    model: Model,
+    checksums: TickChecksums,
 }
 
 impl Snapshot {
-    pub fn new(tick: u64, model: &Model) -> Snapshot {
+    pub fn new(tick: u64, model: &Model, checksums: TickChecksums) -> Snapshot {
         Snapshot {
             tick,
             model: model.clone(),
+            checksums,
         }
     }
 
@@ -123,6 +149,10 @@ impl Snapshot {
     pub fn model(&self) -> &Model {
         &self.model
     }
+
+    pub fn checksums(&self) -> &TickChecksums {
+        &self.checksums
+    }
 }
 
 impl PartialEq for Snapshot {
@@ -143,36 +173,68 @@ impl PartialOrd for Snapshot {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum ConlessClientMessage {
-    ConnectionRequest,
+    // the name is only a wish, the server may change it
+    ConnectionRequest { name: String, spectator: bool },
     ConnectionAbort,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ReliableClientMessage {
     DisconnectRequest,
+    SwitchTeam { team: u64 },
+    Chat { text: String, team_only: bool },
+    Rename { name: String },
+    SetSpectator { spectator: bool },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum UnreliableClientMessage {
-    InputMessage { tick: u64, input: CharacterInput, },
+    // view_tick is the tick of the model the client was showing, used for lag compensation
+    InputMessage { tick: u64, input: CharacterInput, view_tick: u64, },
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum ConlessServerMessage {
-    ConnectionAccept(u64),
+    ConnectionAccept {
+        player_id: u64,
+        map_name: String,
+        map_hash: u64,
+        movement_params: MovementParams,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ReliableServerMessage {
     ConnectionClose,
+    ChatBroadcast(ChatMessage),
+    PlayerEventBroadcast(PlayerEvent),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum PlayerEvent {
+    Joined { player_id: u64, name: String },
+    Left { player_id: u64, name: String },
+    Renamed { player_id: u64, old_name: String, new_name: String },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum UnreliableServerMessage {
-    TimeOutMessage,
     SnapshotMessage(Snapshot),
     InputAck {
         input_tick: u64,
         arrival_tick_instant: TickInstant,
     },
+    StatsMessage(ServerStats),
+}
+
+// What the server measured over the last second, the counters are totals since it started.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct ServerStats {
+    pub ticks_per_second: u64,
+    pub clients: u64,
+    pub rate_limited_packets: u64,
+    pub banned_packets: u64,
+    pub bans: u64,
+    pub input_violations: u64,
+    pub kicks: u64,
 }
\ No newline at end of file
@@ -0,0 +1,119 @@
+use std::time::Instant;
+use std::time::Duration;
+
+use util;
+
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    pub per_second: f64,
+    pub burst: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FloodProtection {
+    // packets per source address without connection
+    pub conless_limit: RateLimit,
+    // packets per connection
+    pub conful_limit: RateLimit,
+    // dropped packets per source address, before it gets banned
+    pub violation_limit: RateLimit,
+    pub ban_duration: Duration,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FloodStats {
+    pub rate_limited_packets: u64,
+    pub banned_packets: u64,
+    pub bans: u64,
+}
+
+#[derive(Debug)]
+pub struct TokenBucket {
+    tokens: f64,
+    last_update: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(limit: &RateLimit, now: Instant) -> TokenBucket {
+        TokenBucket {
+            tokens: limit.burst,
+            last_update: now,
+        }
+    }
+
+    pub fn take(&mut self, limit: &RateLimit, now: Instant) -> bool {
+        self.refill(limit, now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn is_full(&mut self, limit: &RateLimit, now: Instant) -> bool {
+        self.refill(limit, now);
+        self.tokens >= limit.burst
+    }
+
+    fn refill(&mut self, limit: &RateLimit, now: Instant) {
+        if now > self.last_update {
+            let elapsed = util::duration_as_float(now - self.last_update);
+            self.tokens = (self.tokens + elapsed * limit.per_second).min(limit.burst);
+            self.last_update = now;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMIT: RateLimit = RateLimit { per_second: 2.0, burst: 4.0 };
+
+    fn take_all(bucket: &mut TokenBucket, now: Instant) -> u64 {
+        let mut taken = 0;
+        while bucket.take(&LIMIT, now) {
+            taken += 1;
+        }
+        taken
+    }
+
+    #[test]
+    fn buckets_start_with_the_burst() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(&LIMIT, start);
+        assert!(bucket.is_full(&LIMIT, start));
+        assert_eq!(take_all(&mut bucket, start), 4);
+        assert!(!bucket.is_full(&LIMIT, start));
+    }
+
+    #[test]
+    fn buckets_refill_over_time() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(&LIMIT, start);
+        take_all(&mut bucket, start);
+        assert!(!bucket.take(&LIMIT, start + Duration::from_millis(250)));
+        assert!(bucket.take(&LIMIT, start + Duration::from_millis(500)));
+        assert_eq!(take_all(&mut bucket, start + Duration::from_millis(1500)), 2);
+    }
+
+    #[test]
+    fn buckets_refill_up_to_the_burst() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(&LIMIT, start);
+        take_all(&mut bucket, start);
+        let later = start + Duration::from_secs(60);
+        assert!(bucket.is_full(&LIMIT, later));
+        assert_eq!(take_all(&mut bucket, later), 4);
+    }
+
+    #[test]
+    fn time_going_backwards_adds_nothing() {
+        let start = Instant::now() + Duration::from_secs(1);
+        let mut bucket = TokenBucket::new(&LIMIT, start);
+        take_all(&mut bucket, start);
+        assert!(!bucket.take(&LIMIT, start - Duration::from_secs(1)));
+        assert!(bucket.take(&LIMIT, start + Duration::from_millis(500)));
+    }
+}
\ No newline at end of file
@@ -7,6 +7,7 @@ use std::collections::HashMap;
 use std::collections::VecDeque;
 use std::hash::Hash;
 use std::iter;
+use std::mem;
 
 use arrayvec::ArrayVec;
 
@@ -15,8 +16,12 @@ use net::Packable;
 use net::Message;
 use consts;
 use consts::MAX_UNACKED_MESSAGES;
+use consts::MAX_MESSAGE_FRAGMENTS;
 use consts::ACK_DURATION_SIGMA_FACTOR;
 use online_distribution::OnlineDistribution;
+use net::rate_limit::FloodProtection;
+use net::rate_limit::FloodStats;
+use net::rate_limit::TokenBucket;
 
 use self::InternalEvent::*;
 use self::CheckedMessage::*;
@@ -33,6 +38,7 @@ pub trait WrappedUdpSocket<AddrType>: Sized {
 pub enum ConnectionEndReason {
     TimedOut,
     Reset,
+    Banned,
 }
 
 pub enum Event<AddrType, RecvType: Message> {
@@ -96,6 +102,13 @@ struct SentMessage {
     data: ArrayVec<[u8; MAX_MESSAGE_LENGTH]>,
 }
 
+// The parts of a split unreliable message.
+struct Fragments {
+    message: u64,
+    // empty once the message is complete
+    parts: Vec<Option<Vec<u8>>>,
+}
+
 struct Connection<AddrType: Copy> {
     addr: AddrType,
     sent_messages: VecDeque<SentMessage>, // TODO use byte buffer instead
@@ -106,9 +119,14 @@ struct Connection<AddrType: Copy> {
     their_ack: u64,
     their_resend: bool,
     last_recv_time: Instant,
+    last_send_time: Instant,
     last_resend_time: Option<Instant>,
     disconnecting: bool,
     timed_out: bool,
+    packet_bucket: Option<TokenBucket>,
+    next_fragmented_msg_id: u64,
+    // only the newest split message is put together, older ones are given up
+    fragments: Option<Fragments>,
 }
 
 impl<AddrType: Copy> Connection<AddrType> {
@@ -161,6 +179,7 @@ impl<AddrType: Copy> Connection<AddrType> {
         let payload_size = msg.pack(&mut buf[header_size..]).unwrap();
         let msg_size = header_size + payload_size;
         socket.send_to(&buf[..msg_size], self.addr)?;
+        self.last_send_time = now;
 
         let mut data: ArrayVec<[u8; MAX_MESSAGE_LENGTH]> = iter::repeat(0).collect();
         data.truncate(payload_size);
@@ -185,14 +204,91 @@ impl<AddrType: Copy> Connection<AddrType> {
             resend: self.my_resend,
             conful_header: ConfulHeader::Unreliable,
         };
+        let header_size = header.packed_size().unwrap() as usize;
+        let payload_size = msg.packed_size().unwrap() as usize;
+        if header_size + payload_size > MAX_MESSAGE_LENGTH {
+            return self.send_fragments::<M, S>(msg, payload_size, socket);
+        }
         self.my_resend = false;
         let header_size = header.pack(&mut buf).unwrap();
         let payload_size = msg.pack(&mut buf[header_size..]).unwrap();
         let msg_size = header_size + payload_size;
         socket.send_to(&buf[..msg_size], self.addr)?;
+        self.last_send_time = Instant::now();
         Ok(())
     }
 
+    fn send_fragments<M, S>(&mut self, msg: M::Unreliable, payload_size: usize, socket: &mut S)
+        -> io::Result<()>
+    where
+        M: Message,
+        S: WrappedUdpSocket<AddrType>,
+    {
+        let mut payload = vec![0; payload_size];
+        msg.pack(&mut payload).unwrap();
+        let id = self.next_fragmented_msg_id;
+        let header_size = MessageHeader::Conful {
+            ack: self.my_ack,
+            resend: self.my_resend,
+            conful_header: ConfulHeader::Fragment { message: id, index: 0, count: 0 },
+        }.packed_size().unwrap() as usize;
+        let part_size = MAX_MESSAGE_LENGTH - header_size;
+        let count = (payload_size + part_size - 1) / part_size;
+        if count > MAX_MESSAGE_FRAGMENTS {
+            println!("ERROR: Dropping unreliable message of {} bytes, it's too long!", payload_size);
+            return Ok(());
+        }
+        self.next_fragmented_msg_id += 1;
+
+        let mut buf = [0; MAX_MESSAGE_LENGTH];
+        for (index, part) in payload.chunks(part_size).enumerate() {
+            let header = MessageHeader::Conful {
+                ack: self.my_ack,
+                resend: self.my_resend,
+                conful_header: ConfulHeader::Fragment {
+                    message: id,
+                    index: index as u8,
+                    count: count as u8,
+                },
+            };
+            self.my_resend = false;
+            let header_size = header.pack(&mut buf).unwrap();
+            let msg_size = header_size + part.len();
+            buf[header_size..msg_size].copy_from_slice(part);
+            socket.send_to(&buf[..msg_size], self.addr)?;
+        }
+        self.last_send_time = Instant::now();
+        Ok(())
+    }
+
+    // Returns the payload once all parts of the message arrived.
+    fn add_fragment(&mut self, message: u64, index: u8, count: u8, part: &[u8])
+        -> Option<Vec<u8>>
+    {
+        let (index, count) = (index as usize, count as usize);
+        if count > MAX_MESSAGE_FRAGMENTS || index >= count {
+            println!("DEBUG: Received malformed fragment!");
+            return None;
+        }
+        if self.fragments.as_ref().map_or(true, |fragments| fragments.message < message) {
+            self.fragments = Some(Fragments {
+                message,
+                parts: vec![None; count],
+            });
+        }
+        let fragments = self.fragments.as_mut().unwrap();
+        if fragments.message != message || fragments.parts.len() != count {
+            println!("DEBUG: Received late fragment!");
+            return None;
+        }
+        fragments.parts[index] = Some(part.to_vec());
+        if fragments.parts.iter().any(|part| part.is_none()) {
+            return None;
+        }
+        let parts = mem::replace(&mut fragments.parts, Vec::new());
+        Some(parts.into_iter().flat_map(|part| part.unwrap()).collect())
+    }
+
     fn send_ack<S>(&mut self, socket: &mut S) -> io::Result<()>
     where
         S: WrappedUdpSocket<AddrType>,
@@ -208,6 +304,7 @@ impl<AddrType: Copy> Connection<AddrType> {
         self.my_resend = false;
         let header_size = header.pack(&mut buf).unwrap();
         socket.send_to(&buf[..header_size], self.addr)?;
+        self.last_send_time = Instant::now();
         Ok(())
     }
 }
@@ -227,6 +324,8 @@ enum MessageHeader {
 enum ConfulHeader {
     Reliable(u64),
     Unreliable,
+    // part of an unreliable message that doesn't fit in one packet
+    Fragment { message: u64, index: u8, count: u8 },
     Ack,
 }
 
@@ -244,7 +343,15 @@ pub struct ReliableSocket<
     next_tick_time: Instant,
     timeout_duration: Duration,
     timeout_duration_disconnecting: Duration,
+    idle_timeout_duration: Duration,
+    keepalive_interval: Duration,
     event_queue: VecDeque<InternalEvent>,
+    flood_protection: Option<FloodProtection>,
+    conless_buckets: HashMap<AddrType, TokenBucket>,
+    violation_buckets: HashMap<AddrType, TokenBucket>,
+    bans: HashMap<AddrType, Instant>,
+    next_flood_cleanup_time: Instant,
+    flood_stats: FloodStats,
     phantom_send: PhantomData<SendType>,
     phantom_recv: PhantomData<RecvType>,
 }
@@ -256,7 +363,9 @@ impl<
     WrappedUdpSocketType: WrappedUdpSocket<AddrType>
 > ReliableSocket<AddrType, SendType, RecvType, WrappedUdpSocketType> {
     pub fn new(wrapped_udp_socket: WrappedUdpSocketType, ack_timeout: Duration,
-               ack_timeout_disconnecting: Duration, send_con_reset: bool) -> Self {
+               ack_timeout_disconnecting: Duration, idle_timeout: Duration,
+               keepalive_interval: Duration, send_con_reset: bool,
+               flood_protection: Option<FloodProtection>) -> Self {
         ReliableSocket {
             next_connection_id: 0,
             send_con_reset,
@@ -266,7 +375,15 @@ impl<
             next_tick_time: Instant::now(),
             timeout_duration: ack_timeout,
             timeout_duration_disconnecting: ack_timeout_disconnecting,
+            idle_timeout_duration: idle_timeout,
+            keepalive_interval,
             event_queue: VecDeque::new(),
+            flood_protection,
+            conless_buckets: HashMap::new(),
+            violation_buckets: HashMap::new(),
+            bans: HashMap::new(),
+            next_flood_cleanup_time: Instant::now(),
+            flood_stats: Default::default(),
             phantom_send: PhantomData,
             phantom_recv: PhantomData,
         }
@@ -278,6 +395,9 @@ impl<
         }
         let id = self.next_connection_id;
         self.next_connection_id += 1;
+        let now = Instant::now();
+        let packet_bucket = self.flood_protection
+            .map(|fp| TokenBucket::new(&fp.conful_limit, now));
         // TODO avoid memory allocation on new connections
         self.connections.insert(id, Connection {
             addr,
@@ -289,9 +409,13 @@ impl<
             their_ack: 0,
             their_resend: false,
             last_recv_time: Instant::now(),
+            last_send_time: Instant::now(),
             last_resend_time: None,
             disconnecting: false,
             timed_out: false,
+            packet_bucket,
+            next_fragmented_msg_id: 0,
+            fragments: None,
         });
         self.con_ids_by_addr.insert(addr, id);
         id
@@ -326,6 +450,14 @@ impl<
             }
 
             // check if didn't hear anything for too long
+            if now - con.last_recv_time > self.idle_timeout_duration {
+                println!("DEBUG: Connection {} was idle for too long!", con_id);
+                con.timed_out = true;
+                self.event_queue.push_back(ConnectionEnd { con_id, reason: TimedOut });
+                continue;
+            }
+
+            // check if our oldest message didn't get acked for too long
             if let Some(&SentMessage { send_time, .. }) = con.sent_messages.front() {
                 let ack_silence = now - send_time;
                 let timed_out = if con.disconnecting {
@@ -335,6 +467,7 @@ impl<
                 };
                 if timed_out {
                     con.timed_out = true;
+                    self.event_queue.push_back(ConnectionEnd { con_id, reason: TimedOut });
                     continue;
                 }
             }
@@ -373,8 +506,16 @@ impl<
                     }
                 }
                 con.last_resend_time = Some(now);
+                con.last_send_time = now;
                 con.their_resend = false;
             }
+
+            // keep the connection alive if we didn't send anything for a while
+            if now - con.last_send_time > self.keepalive_interval {
+                if let Err(err) = con.send_ack(&mut self.socket) {
+                    self.event_queue.push_back(NetworkError(err));
+                }
+            }
         }
 
         // TODO make flexible
@@ -390,6 +531,10 @@ impl<
         }
     }
 
+    pub fn flood_stats(&self) -> FloodStats {
+        self.flood_stats
+    }
+
     pub fn send_to_conless(&mut self, addr: AddrType, msg: SendType::Conless) {
         let mut buf = [0; MAX_MESSAGE_LENGTH];
         let header = MessageHeader::Conless;
@@ -488,7 +633,35 @@ impl<
     // TODO maybe this wrapper method is not needed and we just return if the time is up
     pub fn wait_event(&mut self, until: Instant) -> Option<Event<AddrType, RecvType>> {
         // first return any queued event
-        if let Some(e) = self.event_queue.pop_front() {
+        if let Some(event) = self.next_queued_event() {
+            return Some(event);
+        }
+
+        // then make sure we read a message if there are any
+        self.socket.set_nonblocking(true).unwrap();
+        let result = self.recv_from(None, None);
+        self.socket.set_nonblocking(false).unwrap();
+        if let Some(_) = result {
+            return result;
+        }
+
+        // if there was no message, wait for one until time out
+        self.recv_from(Some(until), None)
+    }
+
+    // Like wait_event, but never blocks.
+    // The wrapped socket has to be non-blocking already, it's not switched here.
+    // Every datagram read takes one from the budget, including ones that are dropped,
+    // nothing is read once it is used up.
+    pub fn poll_event(&mut self, budget: &mut usize) -> Option<Event<AddrType, RecvType>> {
+        if let Some(event) = self.next_queued_event() {
+            return Some(event);
+        }
+        self.recv_from(None, Some(budget))
+    }
+
+    fn next_queued_event(&mut self) -> Option<Event<AddrType, RecvType>> {
+        while let Some(e) = self.event_queue.pop_front() {
             match e {
                 ConnectionEnd { con_id, reason } => {
                     // connection might have been removed via terminate() in the mean time
@@ -504,24 +677,20 @@ impl<
                 NetworkError(e) => return Some(Event::NetworkError(e)),
             }
         }
-
-        // then make sure we read a message if there are any
-        self.socket.set_nonblocking(true).unwrap();
-        let result = self.recv_from(None);
-        self.socket.set_nonblocking(false).unwrap();
-        if let Some(_) = result {
-            return result;
-        }
-
-        // if there was no message, wait for one until time out
-        self.recv_from(Some(until))
+        None
     }
 
-    // reads messages until there is a valid one or an error occurs
+    // reads messages until there is a valid one, an error occurs or the budget is used up
     // time out errors are transformed into None
-    fn recv_from<'a>(&mut self, until: Option<Instant>) -> Option<Event<AddrType, RecvType>> {
+    fn recv_from<'a>(&mut self, until: Option<Instant>, mut budget: Option<&mut usize>)
+                     -> Option<Event<AddrType, RecvType>> {
         let mut buf = [0; MAX_MESSAGE_LENGTH];
         loop {
+            if let Some(ref mut budget) = budget {
+                if **budget == 0 {
+                    return None;
+                }
+            }
             if let Some(until) = until {
                 let now = Instant::now();
                 if until <= now {
@@ -531,6 +700,12 @@ impl<
             }
             match self.socket.recv_from(&mut buf) {
                 Ok((amount, addr)) => {
+                    if let Some(ref mut budget) = budget {
+                        **budget -= 1;
+                    }
+                    if !self.admit_packet(addr, Instant::now()) {
+                        continue;
+                    }
                     match MessageHeader::unpack(&buf[..amount]) {
                         Ok(header) => {
                             let header_size = header.packed_size().unwrap() as usize; // TODO isn't this constant?
@@ -615,6 +790,76 @@ impl<
         }
     }
 
+    // checks bans and rate limits, returns whether the packet should be handled
+    fn admit_packet(&mut self, addr: AddrType, now: Instant) -> bool {
+        let fp = match self.flood_protection {
+            Some(fp) => fp,
+            None => return true,
+        };
+        if now >= self.next_flood_cleanup_time {
+            self.clean_up_flood_protection(now);
+        }
+
+        if let Some(&ban_end) = self.bans.get(&addr) {
+            if now < ban_end {
+                self.flood_stats.banned_packets += 1;
+                return false;
+            }
+            self.bans.remove(&addr);
+        }
+
+        let admitted = match self.con_ids_by_addr.get(&addr) {
+            Some(con_id) => {
+                let con = self.connections.get_mut(con_id).unwrap();
+                con.packet_bucket.as_mut().unwrap().take(&fp.conful_limit, now)
+            },
+            None => {
+                self.conless_buckets.entry(addr)
+                    .or_insert_with(|| TokenBucket::new(&fp.conless_limit, now))
+                    .take(&fp.conless_limit, now)
+            },
+        };
+        if admitted {
+            return true;
+        }
+
+        self.flood_stats.rate_limited_packets += 1;
+        let violation_allowed = self.violation_buckets.entry(addr)
+            .or_insert_with(|| TokenBucket::new(&fp.violation_limit, now))
+            .take(&fp.violation_limit, now);
+        if !violation_allowed {
+            self.ban(addr, now + fp.ban_duration);
+        }
+        false
+    }
+
+    fn ban(&mut self, addr: AddrType, ban_end: Instant) {
+        self.flood_stats.bans += 1;
+        self.bans.insert(addr, ban_end);
+        self.conless_buckets.remove(&addr);
+        self.violation_buckets.remove(&addr);
+        if let Some(&con_id) = self.con_ids_by_addr.get(&addr) {
+            println!("DEBUG: Banning connection {} for flooding!", con_id);
+            let con = self.connections.get_mut(&con_id).unwrap();
+            if !con.timed_out {
+                con.timed_out = true;
+                self.event_queue.push_back(ConnectionEnd { con_id, reason: Banned });
+            }
+        } else {
+            println!("DEBUG: Banning address without connection for flooding!");
+        }
+    }
+
+    fn clean_up_flood_protection(&mut self, now: Instant) {
+        if let Some(fp) = self.flood_protection {
+            // full buckets behave exactly like new ones, so we can forget them
+            self.conless_buckets.retain(|_, bucket| !bucket.is_full(&fp.conless_limit, now));
+            self.violation_buckets.retain(|_, bucket| !bucket.is_full(&fp.violation_limit, now));
+            self.bans.retain(|_, ban_end| *ban_end > now);
+        }
+        self.next_flood_cleanup_time = now + consts::flood_protection_cleanup_interval();
+    }
+
     fn handle_conmessage(
         &mut self,
         con_id: ConId,
@@ -633,7 +878,7 @@ impl<
                             "DEBUG: Received reliable message from disconnecting connection!"
                         );
                     },
-                    ConfulHeader::Unreliable => {
+                    ConfulHeader::Unreliable | ConfulHeader::Fragment { .. } => {
                         println!(
                             "DEBUG: Received unreliable message from disconnecting connection!"
                         );
@@ -683,6 +928,22 @@ impl<
                             ),
                         }
                     },
+                    ConfulHeader::Fragment { message, index, count } => {
+                        let payload = con.add_fragment(message, index, count, payload_slice)?;
+                        match RecvType::Unreliable::unpack(&payload) {
+                            Ok(umsg) => {
+                                return Some(Event::MessageReceived(
+                                    Conful::<AddrType, RecvType> {
+                                        con_id,
+                                        cmsg: Unreliable::<RecvType>(umsg),
+                                    }
+                                ));
+                            },
+                            Err(e) => println!(
+                                "DEBUG: Received malformed message. Unpack error: {:?}", e
+                            ),
+                        }
+                    },
                     ConfulHeader::Ack => (),
                 }
                 return None;
@@ -693,4 +954,194 @@ impl<
         self.con_ids_by_addr.remove(&con.addr).unwrap();
         Some(Event::DoneDisconnecting(con_id))
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use net::rate_limit::RateLimit;
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    struct TestMessage(Vec<u8>);
+
+    impl Message for TestMessage {
+        type Conless = TestMessage;
+        type Reliable = TestMessage;
+        type Unreliable = TestMessage;
+    }
+
+    // Hands out the queued datagrams and keeps the sent ones.
+    #[derive(Default)]
+    struct MockSocket {
+        incoming: VecDeque<(Vec<u8>, u64)>,
+        sent: Vec<(Vec<u8>, u64)>,
+    }
+
+    impl WrappedUdpSocket<u64> for MockSocket {
+        fn send_to(&mut self, buf: &[u8], addr: u64) -> io::Result<usize> {
+            self.sent.push((buf.to_vec(), addr));
+            Ok(buf.len())
+        }
+
+        fn recv_from(&mut self, buf: &mut [u8]) -> io::Result<(usize, u64)> {
+            match self.incoming.pop_front() {
+                Some((datagram, addr)) => {
+                    buf[..datagram.len()].copy_from_slice(&datagram);
+                    Ok((datagram.len(), addr))
+                },
+                None => Err(io::Error::new(ErrorKind::WouldBlock, "no datagrams queued")),
+            }
+        }
+
+        fn set_nonblocking(&mut self, _: bool) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn set_read_timeout(&mut self, _: Option<Duration>) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    type TestSocket = ReliableSocket<u64, TestMessage, TestMessage, MockSocket>;
+
+    fn socket(flood_protection: Option<FloodProtection>) -> TestSocket {
+        ReliableSocket::new(
+            MockSocket::default(),
+            consts::ack_timeout_duration(),
+            consts::ack_timeout_duration(),
+            consts::idle_timeout_duration(),
+            consts::keepalive_interval(),
+            false,
+            flood_protection,
+        )
+    }
+
+    #[test]
+    fn flooders_get_banned_after_the_violation_burst() {
+        let mut socket = socket(Some(FloodProtection {
+            conless_limit: RateLimit { per_second: 1.0, burst: 2.0 },
+            conful_limit: RateLimit { per_second: 1.0, burst: 2.0 },
+            violation_limit: RateLimit { per_second: 1.0, burst: 3.0 },
+            ban_duration: Duration::from_secs(10),
+        }));
+        let start = Instant::now();
+        assert!(socket.admit_packet(1, start));
+        assert!(socket.admit_packet(1, start));
+        for _ in 0..3 {
+            assert!(!socket.admit_packet(1, start));
+        }
+        assert_eq!(socket.flood_stats().bans, 0);
+        assert!(!socket.admit_packet(1, start));
+        assert_eq!(socket.flood_stats().bans, 1);
+        assert_eq!(socket.flood_stats().rate_limited_packets, 4);
+
+        // the rate limit would let it through again, but the ban lasts
+        assert!(!socket.admit_packet(1, start + Duration::from_secs(5)));
+        assert_eq!(socket.flood_stats().banned_packets, 1);
+        assert!(socket.admit_packet(2, start));
+        assert!(socket.admit_packet(1, start + Duration::from_secs(10)));
+    }
+    const SENDER: u64 = 1;
+    const RECEIVER: u64 = 2;
+
+    // A message too long for one packet, with bytes that tell the parts apart.
+    fn long_message(length: usize) -> Vec<u8> {
+        (0..length).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn send_split(sender: &mut TestSocket, con_id: ConId, payload: Vec<u8>) -> Vec<Vec<u8>> {
+        sender.send_to_unreliable(con_id, TestMessage(payload));
+        sender.socket.sent.drain(..).map(|(datagram, _)| datagram).collect()
+    }
+
+    fn receive(receiver: &mut TestSocket, datagrams: Vec<Vec<u8>>) -> Vec<Vec<u8>> {
+        receiver.socket.incoming.extend(datagrams.into_iter().map(|datagram| (datagram, SENDER)));
+        let mut received = Vec::new();
+        let mut budget = usize::max_value();
+        while let Some(event) = receiver.poll_event(&mut budget) {
+            if let Event::MessageReceived(Conful { cmsg: Unreliable(TestMessage(payload)), .. })
+                    = event {
+                received.push(payload);
+            }
+        }
+        received
+    }
+
+    fn connected_pair() -> (TestSocket, ConId, TestSocket) {
+        let mut sender = socket(None);
+        let con_id = sender.connect(RECEIVER);
+        let mut receiver = socket(None);
+        receiver.connect(SENDER);
+        (sender, con_id, receiver)
+    }
+
+    fn fragment(message: u64, index: u8, count: u8) -> Vec<u8> {
+        let mut buf = [0; MAX_MESSAGE_LENGTH];
+        let header = MessageHeader::Conful {
+            ack: 0,
+            resend: false,
+            conful_header: ConfulHeader::Fragment { message, index, count },
+        };
+        let header_size = header.pack(&mut buf).unwrap();
+        let mut datagram = buf[..header_size].to_vec();
+        datagram.extend_from_slice(&[1, 2, 3]);
+        datagram
+    }
+
+    #[test]
+    fn fragments_arriving_out_of_order_are_put_together() {
+        let (mut sender, con_id, mut receiver) = connected_pair();
+        let payload = long_message(3 * MAX_MESSAGE_LENGTH);
+        let mut datagrams = send_split(&mut sender, con_id, payload.clone());
+        assert!(datagrams.len() > 3);
+        assert!(datagrams.iter().all(|datagram| datagram.len() <= MAX_MESSAGE_LENGTH));
+        datagrams.reverse();
+        datagrams.swap(0, 1);
+        assert_eq!(receive(&mut receiver, datagrams), vec![payload]);
+    }
+
+    #[test]
+    fn late_fragments_of_older_messages_are_dropped() {
+        let (mut sender, con_id, mut receiver) = connected_pair();
+        let first = long_message(2 * MAX_MESSAGE_LENGTH);
+        let second = long_message(3 * MAX_MESSAGE_LENGTH);
+        let mut first_datagrams = send_split(&mut sender, con_id, first);
+        let second_datagrams = send_split(&mut sender, con_id, second.clone());
+        // one part of the first message is lost, another one arrives after the second message
+        first_datagrams.remove(0);
+        let late = first_datagrams.pop().unwrap();
+        assert!(receive(&mut receiver, first_datagrams).is_empty());
+        assert_eq!(receive(&mut receiver, second_datagrams), vec![second]);
+        assert!(receive(&mut receiver, vec![late]).is_empty());
+    }
+
+    #[test]
+    fn messages_over_the_fragment_limit_are_not_sent() {
+        let (mut sender, con_id, _) = connected_pair();
+        let payload = long_message(MAX_MESSAGE_FRAGMENTS * MAX_MESSAGE_LENGTH);
+        assert!(send_split(&mut sender, con_id, payload).is_empty());
+        // the next message still gets through
+        let payload = long_message(2 * MAX_MESSAGE_LENGTH);
+        assert!(!send_split(&mut sender, con_id, payload).is_empty());
+    }
+
+    #[test]
+    fn malformed_fragment_counts_are_dropped() {
+        let (mut sender, con_id, mut receiver) = connected_pair();
+        let too_many = (MAX_MESSAGE_FRAGMENTS + 1) as u8;
+        let malformed = vec![
+            fragment(5, 0, too_many),
+            fragment(5, 2, 2),
+            fragment(5, 0, 0),
+            fragment(5, 255, 255),
+        ];
+        assert!(receive(&mut receiver, malformed).is_empty());
+        // a part of a message can't change its count either
+        let payload = long_message(2 * MAX_MESSAGE_LENGTH);
+        let mut datagrams = send_split(&mut sender, con_id, payload.clone());
+        let count = datagrams.len() as u8;
+        datagrams.insert(1, fragment(0, count, count + 1));
+        assert_eq!(receive(&mut receiver, datagrams), vec![payload]);
+    }
 }
\ No newline at end of file
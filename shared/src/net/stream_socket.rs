@@ -0,0 +1,341 @@
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::io::ErrorKind;
+use std::net::TcpStream;
+use std::net::TcpListener;
+use std::net::Shutdown;
+use std::net::SocketAddr;
+use std::net::ToSocketAddrs;
+use std::time::Duration;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Arc;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::sync::mpsc::SyncSender;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::TrySendError;
+use std::sync::mpsc::TryRecvError;
+use std::sync::mpsc::RecvTimeoutError;
+use std::thread;
+use std::iter;
+
+use arrayvec::ArrayVec;
+
+use consts;
+use net::MAX_MESSAGE_LENGTH;
+use net::socket::WrappedUdpSocket;
+use net::event_poll::Waker;
+
+use self::StreamEvent::*;
+
+// Frames are sent as a big-endian u16 length followed by the payload.
+const FRAME_HEADER_LENGTH: usize = 2;
+
+enum StreamEvent<AddrType> {
+    Opened(AddrType, Stream),
+    Frame(AddrType, ArrayVec<[u8; MAX_MESSAGE_LENGTH]>),
+    Closed(AddrType),
+}
+
+struct Stream {
+    stream: TcpStream,
+    // written by the stream's writing thread, so slow peers don't hold up the others
+    outgoing: SyncSender<Vec<u8>>,
+}
+
+impl Stream {
+    fn open(stream: TcpStream) -> io::Result<Stream> {
+        let write_stream = stream.try_clone()?;
+        let (outgoing, frames) = mpsc::sync_channel(consts::STREAM_OUTGOING_FRAMES);
+        thread::spawn(move || write_frames(write_stream, frames));
+        Ok(Stream {
+            stream,
+            outgoing,
+        })
+    }
+}
+
+// Sends datagrams as length-prefixed frames over TCP,
+// for networks that block UDP.
+// Every stream is read by its own thread, which optionally wakes up an EventPoll,
+// and written by another one.
+pub struct StreamSocket<AddrType> {
+    streams: HashMap<AddrType, Stream>,
+    events: Receiver<StreamEvent<AddrType>>,
+    nonblocking: bool,
+    read_timeout: Option<Duration>,
+}
+
+impl StreamSocket<SocketAddr> {
+    pub fn listen<A: ToSocketAddrs>(addr: A, waker: Option<Waker>)
+        -> io::Result<StreamSocket<SocketAddr>>
+    {
+        let listener = TcpListener::bind(addr)?;
+        let (sender, receiver) = mpsc::sync_channel(consts::STREAM_INCOMING_EVENTS);
+        thread::spawn(move || accept_streams(listener, sender, waker));
+        Ok(StreamSocket {
+            streams: HashMap::new(),
+            events: receiver,
+            nonblocking: true,
+            read_timeout: None,
+        })
+    }
+}
+
+impl StreamSocket<()> {
+    pub fn connect(addr: SocketAddr) -> io::Result<StreamSocket<()>> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        stream.set_write_timeout(Some(consts::stream_write_timeout()))?;
+        let (sender, receiver) = mpsc::sync_channel(consts::STREAM_INCOMING_EVENTS);
+        let read_stream = stream.try_clone()?;
+        read_stream.set_read_timeout(Some(consts::idle_timeout_duration()))?;
+        let stream = Stream::open(stream)?;
+        thread::spawn(move || read_frames(read_stream, (), sender, None));
+        Ok(StreamSocket {
+            streams: iter::once(((), stream)).collect(),
+            events: receiver,
+            nonblocking: false,
+            read_timeout: None,
+        })
+    }
+}
+
+impl<AddrType: Copy + Hash + Eq> WrappedUdpSocket<AddrType> for StreamSocket<AddrType> {
+    fn send_to(&mut self, buf: &[u8], addr: AddrType) -> io::Result<usize> {
+        let mut frame = Vec::with_capacity(FRAME_HEADER_LENGTH + buf.len());
+        frame.push((buf.len() >> 8) as u8);
+        frame.push(buf.len() as u8);
+        frame.extend_from_slice(buf);
+        let broken = match self.streams.get(&addr) {
+            Some(stream) => match stream.outgoing.try_send(frame) {
+                Ok(()) => false,
+                Err(TrySendError::Full(_)) => {
+                    println!("DEBUG: Stream peer doesn't keep up!");
+                    true
+                },
+                Err(TrySendError::Disconnected(_)) => true,
+            },
+            None => false,
+        };
+        // like with UDP, a message to a broken or unknown peer is just lost
+        if broken {
+            println!("DEBUG: Dropping broken stream!");
+            if let Some(stream) = self.streams.remove(&addr) {
+                // this also ends the reading and writing threads
+                stream.stream.shutdown(Shutdown::Both).ok();
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn recv_from(&mut self, buf: &mut [u8]) -> io::Result<(usize, AddrType)> {
+        loop {
+            let event = if self.nonblocking {
+                match self.events.try_recv() {
+                    Ok(event) => event,
+                    Err(TryRecvError::Empty) => return Err(ErrorKind::WouldBlock.into()),
+                    Err(TryRecvError::Disconnected) => {
+                        return Err(ErrorKind::NotConnected.into())
+                    },
+                }
+            } else if let Some(timeout) = self.read_timeout {
+                match self.events.recv_timeout(timeout) {
+                    Ok(event) => event,
+                    Err(RecvTimeoutError::Timeout) => return Err(ErrorKind::TimedOut.into()),
+                    Err(RecvTimeoutError::Disconnected) => {
+                        return Err(ErrorKind::NotConnected.into())
+                    },
+                }
+            } else {
+                match self.events.recv() {
+                    Ok(event) => event,
+                    Err(_) => return Err(ErrorKind::NotConnected.into()),
+                }
+            };
+            match event {
+                Opened(addr, stream) => {
+                    self.streams.insert(addr, stream);
+                },
+                Frame(addr, data) => {
+                    buf[..data.len()].copy_from_slice(&data);
+                    return Ok((data.len(), addr));
+                },
+                Closed(addr) => {
+                    self.streams.remove(&addr);
+                },
+            }
+        }
+    }
+
+    fn set_nonblocking(&mut self, nonblocking: bool) -> io::Result<()> {
+        self.nonblocking = nonblocking;
+        Ok(())
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.read_timeout = timeout;
+        Ok(())
+    }
+}
+
+fn accept_streams(listener: TcpListener, events: SyncSender<StreamEvent<SocketAddr>>,
+                  waker: Option<Waker>) {
+    // counts the reading threads, which end when their stream is closed
+    let open_streams = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                println!("DEBUG: Failed to accept stream: {:?}", e);
+                continue;
+            },
+        };
+        if open_streams.load(Ordering::SeqCst) >= consts::MAX_STREAMS {
+            println!("DEBUG: Refusing stream, too many are open!");
+            stream.shutdown(Shutdown::Both).ok();
+            continue;
+        }
+        let opened = stream.peer_addr()
+            .and_then(|addr| stream.set_nodelay(true).map(|_| addr))
+            .and_then(|addr| {
+                stream.set_write_timeout(Some(consts::stream_write_timeout())).map(|_| addr)
+            })
+            .and_then(|addr| stream.try_clone().map(|read_stream| (addr, read_stream)))
+            .and_then(|(addr, read_stream)| {
+                // silent peers are dropped like idle connections
+                read_stream.set_read_timeout(Some(consts::idle_timeout_duration()))
+                    .map(|_| (addr, read_stream))
+            })
+            .and_then(|(addr, read_stream)| {
+                Stream::open(stream).map(|stream| (addr, read_stream, stream))
+            });
+        let (addr, read_stream, stream) = match opened {
+            Ok(opened) => opened,
+            Err(e) => {
+                println!("DEBUG: Failed to set up stream: {:?}", e);
+                continue;
+            },
+        };
+        if events.send(Opened(addr, stream)).is_err() {
+            // the socket was dropped
+            return;
+        }
+        let events = events.clone();
+        let waker = waker.clone();
+        let open_streams = open_streams.clone();
+        open_streams.fetch_add(1, Ordering::SeqCst);
+        thread::spawn(move || {
+            read_frames(read_stream, addr, events, waker);
+            open_streams.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+}
+
+// Blocks while the socket has too many unhandled events,
+// which makes TCP slow down the peer instead of filling up our memory.
+fn read_frames<AddrType: Copy>(mut stream: TcpStream, addr: AddrType,
+                               events: SyncSender<StreamEvent<AddrType>>, waker: Option<Waker>) {
+    loop {
+        let data = match read_frame(&mut stream) {
+            Ok(data) => data,
+            Err(e) => {
+                if e.kind() == ErrorKind::InvalidData {
+                    println!("DEBUG: Received too long frame!");
+                }
+                break;
+            },
+        };
+        if events.send(Frame(addr, data)).is_err() {
+            // the socket was dropped
+            return;
+        }
+        if let Some(ref waker) = waker {
+            waker.wake();
+        }
+    }
+    if events.send(Closed(addr)).is_ok() {
+        if let Some(ref waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+fn read_frame<R: Read>(reader: &mut R) -> io::Result<ArrayVec<[u8; MAX_MESSAGE_LENGTH]>> {
+    let mut header = [0; FRAME_HEADER_LENGTH];
+    reader.read_exact(&mut header)?;
+    let length = ((header[0] as usize) << 8) | header[1] as usize;
+    if length > MAX_MESSAGE_LENGTH {
+        return Err(ErrorKind::InvalidData.into());
+    }
+    let mut data: ArrayVec<[u8; MAX_MESSAGE_LENGTH]> = iter::repeat(0).collect();
+    data.truncate(length);
+    reader.read_exact(&mut data)?;
+    Ok(data)
+}
+
+fn write_frames(mut stream: TcpStream, frames: Receiver<Vec<u8>>) {
+    // ends when the socket drops the stream
+    for frame in frames {
+        if stream.write_all(&frame).is_err() {
+            // the peer is gone or stalled, this also ends the reading thread
+            stream.shutdown(Shutdown::Both).ok();
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // hands out at most a few bytes per read, like a slow TCP connection
+    struct Trickle<'a> {
+        data: &'a [u8],
+        chunk: usize,
+    }
+
+    impl<'a> Read for Trickle<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let length = self.chunk.min(buf.len()).min(self.data.len());
+            buf[..length].copy_from_slice(&self.data[..length]);
+            self.data = &self.data[length..];
+            Ok(length)
+        }
+    }
+
+    #[test]
+    fn frames_split_across_reads() {
+        let bytes = [0, 3, 1, 2, 3, 0, 0, 0, 1, 4];
+        let mut reader = Trickle { data: &bytes, chunk: 1 };
+        assert_eq!(&read_frame(&mut reader).unwrap()[..], &[1, 2, 3]);
+        assert_eq!(&read_frame(&mut reader).unwrap()[..], &[]);
+        assert_eq!(&read_frame(&mut reader).unwrap()[..], &[4]);
+        assert_eq!(read_frame(&mut reader).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn truncated_frames_are_errors() {
+        let bytes = [0, 3, 1, 2];
+        let mut reader = Trickle { data: &bytes, chunk: 3 };
+        assert_eq!(read_frame(&mut reader).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn too_long_frames_are_rejected() {
+        let length = MAX_MESSAGE_LENGTH + 1;
+        let mut bytes = vec![(length >> 8) as u8, length as u8];
+        bytes.extend(iter::repeat(0).take(length));
+        let mut reader = Trickle { data: &bytes, chunk: 1000 };
+        assert_eq!(read_frame(&mut reader).unwrap_err().kind(), ErrorKind::InvalidData);
+
+        let length = MAX_MESSAGE_LENGTH;
+        let mut bytes = vec![(length >> 8) as u8, length as u8];
+        bytes.extend(iter::repeat(7).take(length));
+        let mut reader = Trickle { data: &bytes, chunk: 1000 };
+        assert_eq!(read_frame(&mut reader).unwrap().len(), MAX_MESSAGE_LENGTH);
+    }
+}
\ No newline at end of file
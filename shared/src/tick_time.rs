@@ -5,6 +5,7 @@ use std::ops::Mul;
 use std::ops::Div;
 
 use util;
+use math::FixedPoint;
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct TickInstant {
@@ -86,6 +87,22 @@ impl TickRate {
     pub fn per_second(&self) -> u64 {
         self.per_second
     }
+
+    // Rounds to the nearest tick.
+    pub fn ticks(&self, seconds: f64) -> u64 {
+        (seconds * self.per_second as f64).round() as u64
+    }
+
+    // Converts a speed in units per second to units per tick.
+    pub fn per_tick(&self, per_second: f64) -> FixedPoint {
+        FixedPoint::from_float(per_second / self.per_second as f64)
+    }
+
+    // Converts an acceleration in units per second squared to units per tick squared.
+    pub fn per_tick_squared(&self, per_second_squared: f64) -> FixedPoint {
+        let per_second = self.per_second as f64;
+        FixedPoint::from_float(per_second_squared / (per_second * per_second))
+    }
 }
 
 impl Mul<Duration> for TickRate {
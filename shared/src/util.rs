@@ -1,7 +1,11 @@
 use std::time::Duration;
 use std::time::Instant;
+use std::io;
+use std::io::Write;
 
 use cgmath::Vector3;
+use serde::Serialize;
+use bincode;
 
 pub fn duration_as_float(duration: Duration) -> f64 {
     duration.as_secs() as f64 + duration.subsec_nanos() as f64 * 1e-9
@@ -13,6 +17,65 @@ pub fn duration_from_float(duration_float: f64) -> Duration {
     Duration::new(secs, nanos)
 }
 
+// 64-bit FNV-1a, stable across platforms and releases unlike std's hasher
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hasher = Fnv1a::new();
+    hasher.update(bytes);
+    hasher.finish()
+}
+
+// Incremental version of fnv1a, values are hashed in their serialized form.
+pub struct Fnv1a {
+    hash: u64,
+}
+
+impl Fnv1a {
+    pub fn new() -> Fnv1a {
+        Fnv1a {
+            hash: 0xcbf29ce484222325,
+        }
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.hash ^= byte as u64;
+            self.hash = self.hash.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    // Don't use this for anything containing a HashMap, its serialization order is random.
+    pub fn add<T: Serialize>(&mut self, value: &T) {
+        bincode::serialize_into(self, value).expect("Could not serialize value for hashing!");
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+impl Write for Fnv1a {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Removes control characters and surrounding whitespace from text sent by players.
+// Returns None if nothing is left or the text is longer than max_length characters.
+pub fn sanitize_text(text: &str, max_length: usize) -> Option<String> {
+    let text: String = text.chars().filter(|c| !c.is_control()).collect();
+    let text = text.trim();
+    if text.is_empty() || text.chars().count() > max_length {
+        None
+    } else {
+        Some(String::from(text))
+    }
+}
+
 pub trait Mix {
     fn mix(&self, other: &Self, ratio: f64) -> Self;
 }